@@ -0,0 +1,253 @@
+//! Per-user settings for the CLI, read from a TOML file
+//!
+//! ```toml
+//! provider = "openweathermap"
+//! owm_api_key = "..."
+//! default_profile = "me"
+//!
+//! [profiles.me]
+//! sex = "female"
+//! feel = "runs_cold"
+//! duration = "45m"
+//! location = "home"
+//!
+//! [locations.home]
+//! zip = "02144"
+//! country = "US"
+//!
+//! [cache]
+//! ttl_minutes = 30
+//! ```
+use crate::LocationArgs;
+use anyhow::{anyhow, Context, Result};
+use chrono::Duration;
+use picker::inputs::{Feel, Intensity, Sex, UserPreferences};
+use picker::providers::{CacheSettings, ProviderKind};
+use picker::units::{RunDuration, Units};
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where to get the weather from
+    pub provider: Option<ProviderKind>,
+    pub owm_api_key: Option<String>,
+    /// JSON file of canned conditions, used with the fixture provider
+    pub fixture: Option<PathBuf>,
+    /// Refuse to use weather older than this
+    pub max_weather_age_minutes: Option<i64>,
+    /// Profile to use when none is given on the command line
+    pub default_profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+    pub locations: BTreeMap<String, LocationArgs>,
+    pub cache: Cache,
+}
+
+/// How to cache weather fetched from providers. Anything left out falls back to the defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cache {
+    pub enabled: bool,
+    /// Directory to cache weather in [default: ~/.cache/wtwr on Linux]
+    pub dir: Option<PathBuf>,
+    /// How long to use cached weather before fetching it again
+    pub ttl_minutes: Option<i64>,
+    /// How old cached weather can be and still be used when fetching fails
+    pub max_stale_minutes: Option<i64>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: None,
+            ttl_minutes: None,
+            max_stale_minutes: None,
+        }
+    }
+}
+
+impl Cache {
+    /// Returns `None` if caching is turned off or there's nowhere to cache
+    pub fn settings(&self) -> Option<CacheSettings> {
+        if !self.enabled {
+            return None;
+        }
+        let dir = self
+            .dir
+            .clone()
+            .or_else(|| dirs::cache_dir().map(|dir| dir.join("wtwr")))?;
+        let mut settings = CacheSettings::new(dir);
+        if let Some(minutes) = self.ttl_minutes {
+            settings.ttl = Duration::minutes(minutes);
+        }
+        if let Some(minutes) = self.max_stale_minutes {
+            settings.max_stale = Duration::minutes(minutes);
+        }
+        Some(settings)
+    }
+}
+
+/// A runner's usual preferences. Anything left out falls back to the defaults.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub sex: Option<Sex>,
+    pub intensity: Option<Intensity>,
+    pub feel: Option<Feel>,
+    pub units: Option<Units>,
+    /// How long a usual run lasts
+    pub duration: Option<RunDuration>,
+    /// Name of the saved location to use when none is given on the command line
+    pub location: Option<String>,
+}
+
+impl Profile {
+    pub fn preferences(&self) -> UserPreferences {
+        UserPreferences {
+            sex: self.sex.unwrap_or_default(),
+            intensity: self.intensity.unwrap_or_default(),
+            feel: self.feel.unwrap_or_default(),
+            units: self.units.unwrap_or_default(),
+            duration: self.duration,
+        }
+    }
+}
+
+// List the keys of a map for error messages
+fn names<T>(map: &BTreeMap<String, T>) -> String {
+    if map.is_empty() {
+        return "none".to_string();
+    }
+    map.keys()
+        .map(String::as_str)
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
+impl Config {
+    /// `~/.config/wtwr/config.toml` on Linux, or the equivalent on other platforms
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("wtwr").join("config.toml"))
+    }
+
+    /// Load the config file at `path`, or at the default path if none is given
+    ///
+    /// It's fine for there to be no file at the default path, in which case an empty config is
+    /// returned.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Self::default())
+            }
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read config file {}", path.display()))
+            }
+        };
+        toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// Look up a profile by name, falling back to the default profile if no name is given
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => self.profiles.get(name).cloned().ok_or_else(|| {
+                anyhow!(
+                    "No profile named '{}' in the config file. Available profiles: {}",
+                    name,
+                    names(&self.profiles)
+                )
+            }),
+            None => Ok(Profile::default()),
+        }
+    }
+
+    pub fn location(&self, name: &str) -> Result<&LocationArgs> {
+        self.locations.get(name).ok_or_else(|| {
+            anyhow!(
+                "No location named '{}' in the config file. Available locations: {}",
+                name,
+                names(&self.locations)
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles() {
+        let config: Config = toml::from_str(
+            r#"
+            default_profile = "me"
+
+            [profiles.me]
+            sex = "female"
+            intensity = "long_run"
+            duration = "1h45m"
+            location = "home"
+
+            [profiles.coach]
+            units = "metric"
+
+            [locations.home]
+            zip = "02144"
+            "#,
+        )
+        .unwrap();
+
+        let profile = config.profile(None).unwrap();
+        assert_eq!(Sex::Female, profile.preferences().sex);
+        assert_eq!(Intensity::LongRun, profile.preferences().intensity);
+        assert_eq!(Feel::Average, profile.preferences().feel);
+        assert_eq!(
+            Some(RunDuration::from_minutes(105)),
+            profile.preferences().duration
+        );
+        assert!(config.location(&profile.location.unwrap()).is_ok());
+
+        let profile = config.profile(Some("coach")).unwrap();
+        assert_eq!(Units::Metric, profile.preferences().units);
+
+        let err = config.profile(Some("nobody")).unwrap_err();
+        assert_eq!(
+            "No profile named 'nobody' in the config file. Available profiles: coach, me",
+            err.to_string()
+        );
+        assert!(config.location("work").is_err());
+    }
+
+    #[test]
+    fn test_cache() {
+        let config: Config = toml::from_str(
+            r#"
+            [cache]
+            dir = "/tmp/wtwr"
+            ttl_minutes = 30
+            "#,
+        )
+        .unwrap();
+        let settings = config.cache.settings().unwrap();
+        assert_eq!(Path::new("/tmp/wtwr"), settings.dir);
+        assert_eq!(Duration::minutes(30), settings.ttl);
+        assert_eq!(Duration::hours(3), settings.max_stale);
+
+        let config: Config = toml::from_str("cache = { enabled = false }").unwrap();
+        assert!(config.cache.settings().is_none());
+    }
+}
@@ -1,36 +1,508 @@
-use anyhow::{anyhow, Result};
-use openweather::LocationSpecifier;
+mod config;
+mod when;
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{Duration, NaiveDateTime, Utc};
+use config::{Config, Profile};
 use picker::{
-    gear::Outfit,
-    inputs::{RunParameters, UserPreferences},
-    weather,
+    air::AirQuality,
+    gear::{Explanation, GearCatalog, Outfit},
+    humidity::Comfort,
+    inputs::{Feel, Intensity, RunParameters, Sex, UserPreferences},
+    planner::Plan,
+    providers::{Location, ProviderKind, ProviderSettings, WeatherProvider},
+    safety::{self, Warning},
+    sun::UvCategory,
+    trend::Trend,
+    units::{DisplayUnits, Distance, Pace, RunDuration, Speed, Temperature, Units},
+    weather::{self, Conditions, Freshness, TimeOfDay, Weather, Wind},
 };
+use serde_derive::{Deserialize, Serialize};
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+use when::When;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "wtwr",
+    about = "Pick a running outfit based on the current weather"
+)]
+struct Opt {
+    #[structopt(flatten)]
+    location: LocationArgs,
+    #[structopt(flatten)]
+    conditions: ConditionsArgs,
+    /// Use the forecast for an upcoming run instead of the current weather, e.g. "tomorrow 6am"
+    /// or "saturday morning"
+    #[structopt(long, conflicts_with_all = &["temperature", "stdin"])]
+    at: Option<When>,
+    /// Name of a location saved in the config file
+    #[structopt(long = "location", conflicts_with_all = &["zip", "city", "lat"])]
+    location_name: Option<String>,
+    /// Name of a profile in the config file. Flags override the profile's settings.
+    #[structopt(long, short)]
+    profile: Option<String>,
+    /// Where to get the weather from: openweathermap, nws, open-meteo, or
+    /// fixture [default: openweathermap]
+    #[structopt(long)]
+    provider: Option<ProviderKind>,
+    /// JSON file of canned conditions, used with the fixture provider
+    #[structopt(long, parse(from_os_str))]
+    fixture: Option<PathBuf>,
+    /// Refuse to use weather older than this many minutes [default: 180]
+    #[structopt(long)]
+    max_age: Option<i64>,
+    /// Always fetch the weather instead of using recently cached weather
+    #[structopt(long)]
+    no_cache: bool,
+    /// Config file to read profiles, locations, and the OpenWeatherMap API key from
+    #[structopt(long, env = "WTWR_CONFIG", parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// male or female [default: male]
+    #[structopt(long)]
+    sex: Option<Sex>,
+    /// long-run, average, workout, or race [default: average]
+    #[structopt(long)]
+    intensity: Option<Intensity>,
+    /// runs-warm, average, or runs-cold [default: average]
+    #[structopt(long)]
+    feel: Option<Feel>,
+    /// imperial or metric [default: imperial]
+    #[structopt(long)]
+    units: Option<Units>,
+    /// How long the run will last, e.g. 45m, 1h30m, or 1:30
+    #[structopt(long, conflicts_with = "distance")]
+    duration: Option<RunDuration>,
+    /// How far the run is, e.g. 6mi or 10km. Used with --pace to work out how long it will last.
+    #[structopt(long, requires = "pace")]
+    distance: Option<Distance>,
+    /// Pace per mile, or per kilometer with a /km suffix, e.g. 8:30 or 5:15/km
+    #[structopt(long, requires = "distance")]
+    pace: Option<Pace>,
+    /// Output format: text or json
+    #[structopt(long, default_value = "text")]
+    format: Format,
+    /// Gear catalog to pick from instead of the built-in one (TOML or JSON)
+    #[structopt(long, env = "WTWR_CATALOG", parse(from_os_str))]
+    catalog: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Also show why each piece of gear was or wasn't picked
+    Explain,
+    /// Find the best time to run in the upcoming forecast, and what to wear then
+    Plan {
+        /// How many hours ahead to look
+        #[structopt(long, default_value = "24")]
+        hours: u32,
+    },
+}
+
+/// Where to get the weather for. Also used for locations saved in the config file.
+#[derive(Debug, Default, StructOpt, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocationArgs {
+    /// ZIP or postal code
+    #[structopt(long, conflicts_with_all = &["city", "lat"])]
+    zip: Option<String>,
+    /// City name
+    #[structopt(long, conflicts_with_all = &["zip", "lat"])]
+    city: Option<String>,
+    /// Two-letter country code, used with --zip or --city [default: US]
+    #[structopt(long)]
+    country: Option<String>,
+    /// Latitude in degrees
+    #[structopt(long, requires = "lon", allow_hyphen_values = true)]
+    lat: Option<f32>,
+    /// Longitude in degrees
+    #[structopt(long, requires = "lat", allow_hyphen_values = true)]
+    lon: Option<f32>,
+}
+
+impl LocationArgs {
+    /// Returns `None` if no location was given
+    fn to_location(&self) -> Option<Location> {
+        let country = self.country.clone().unwrap_or_else(|| "US".to_string());
+        if let Some(zip) = &self.zip {
+            return Some(Location::ZipCode {
+                zip: zip.clone(),
+                country,
+            });
+        }
+        if let Some(city) = &self.city {
+            return Some(Location::City {
+                name: city.clone(),
+                country,
+            });
+        }
+        if let (Some(lat), Some(lon)) = (self.lat, self.lon) {
+            return Some(Location::Coordinates { lat, lon });
+        }
+        None
+    }
+}
+
+// Pick the location from the command line, falling back to a location saved in the config file
+fn resolve_location(opt: &Opt, config: &Config, profile: &Profile) -> Result<Location> {
+    if let Some(loc) = opt.location.to_location() {
+        return Ok(loc);
+    }
+    let name = match opt.location_name.as_deref().or(profile.location.as_deref()) {
+        Some(name) => name,
+        None => bail!(
+            "No location given. Use --zip, --city, --lat and --lon, or --location, or set \
+            a location in your profile."
+        ),
+    };
+    config.location(name)?.to_location().ok_or_else(|| {
+        anyhow!(
+            "Saved location '{}' needs a zip, city, or lat and lon",
+            name
+        )
+    })
+}
+
+/// Conditions to use instead of fetching the current weather
+#[derive(Debug, StructOpt)]
+struct ConditionsArgs {
+    /// Skip fetching the weather and use this temperature instead, e.g. 45F or 7C
+    #[structopt(long, allow_hyphen_values = true)]
+    temperature: Option<Temperature>,
+    /// clear, partly-cloudy, overcast, fog, haze, drizzle, rain, heavy-rain, thunderstorm,
+    /// freezing-rain, sleet, or snow. Used with --temperature.
+    #[structopt(long, requires = "temperature")]
+    weather: Option<Weather>,
+    /// calm, light, or heavy. Used with --temperature.
+    #[structopt(long, requires = "temperature")]
+    wind: Option<Wind>,
+    /// Wind speed, e.g. 12mph or 20km/h. Used with --temperature instead of --wind.
+    #[structopt(long, requires = "temperature", conflicts_with = "wind")]
+    wind_speed: Option<Speed>,
+    /// Relative humidity in percent. Used with --temperature.
+    #[structopt(long, requires = "temperature")]
+    humidity: Option<f32>,
+    /// UV index. Estimated from the weather and time of day if not given. Used with --temperature.
+    #[structopt(long, requires = "temperature")]
+    uv_index: Option<f32>,
+    /// morning, daytime, evening, or night. Used with --temperature.
+    #[structopt(long, requires = "temperature")]
+    time: Option<TimeOfDay>,
+    /// Skip fetching the weather and read conditions as JSON from stdin instead
+    #[structopt(long, conflicts_with = "temperature")]
+    stdin: bool,
+    /// US air quality index to use instead of fetching it
+    #[structopt(long)]
+    aqi: Option<u16>,
+    /// Fine particulate matter (PM2.5) in µg/m³ to use instead of fetching the air quality
+    #[structopt(long, conflicts_with = "aqi")]
+    pm25: Option<f32>,
+}
+
+impl ConditionsArgs {
+    /// Returns the manually supplied air quality, if any
+    fn to_air_quality(&self) -> Option<AirQuality> {
+        self.aqi
+            .map(AirQuality::from_aqi)
+            .or_else(|| self.pm25.map(AirQuality::from_pm2_5))
+    }
+
+    /// Returns the manually supplied conditions, if any
+    fn to_conditions(&self) -> Result<Option<Conditions>> {
+        if self.stdin {
+            let conditions: Conditions = serde_json::from_reader(io::stdin())
+                .map_err(|e| anyhow!("Failed to parse conditions from stdin: {}", e))?;
+            return Ok(Some(conditions));
+        }
+        Ok(self.temperature.map(|temperature| Conditions {
+            temperature,
+            weather: self.weather.unwrap_or_default(),
+            wind: self
+                .wind_speed
+                .map(Wind::from_speed)
+                .or(self.wind)
+                .unwrap_or_default(),
+            time: self.time.unwrap_or_default(),
+            humidity: self.humidity,
+            uv_index: self.uv_index,
+            wind_speed: self.wind_speed,
+            ..Conditions::default()
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => bail!("'{}' is not a valid format. Expected one of: text, json", s),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    /// Local time of the run, if using the forecast
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run_time: Option<NaiveDateTime>,
+    /// How old the weather is, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weather_age_minutes: Option<i64>,
+    /// Dangers to know about before heading out, most severe first
+    #[serde(skip_serializing_if = "<[Warning]>::is_empty")]
+    warnings: &'a [Warning],
+    parameters: &'a RunParameters,
+    outfit: &'a Outfit,
+    /// How the weather changes over the run, if it's known how long the run lasts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trend: Option<&'a Trend>,
+    /// How humid it feels, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comfort: Option<Comfort>,
+    /// UV index from the weather provider, or estimated from the sun and clouds
+    uv_index: f32,
+    uv_category: UvCategory,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explanation: Option<&'a Explanation>,
+}
 
 fn main() -> Result<()> {
-    let owm_api_key = get_owm_key()?;
+    let opt = Opt::from_args();
+    let config = Config::load(opt.config.as_deref())?;
+    let profile = config.profile(opt.profile.as_deref())?;
 
-    let loc = LocationSpecifier::ZipCode {
-        zip: "02144".to_string(),
-        country: "US".to_string(),
+    let defaults = profile.preferences();
+    // A distance and pace work out to a duration
+    let duration = match (opt.distance, opt.pace) {
+        (Some(distance), Some(pace)) => Some(pace.duration(distance)?),
+        _ => opt.duration,
+    };
+    let preferences = UserPreferences {
+        sex: opt.sex.unwrap_or(defaults.sex),
+        intensity: opt.intensity.unwrap_or(defaults.intensity),
+        feel: opt.feel.unwrap_or(defaults.feel),
+        units: opt.units.unwrap_or(defaults.units),
+        duration: duration.or(defaults.duration),
     };
+    let catalog = match &opt.catalog {
+        Some(path) => GearCatalog::load(path)?,
+        None => GearCatalog::default(),
+    };
+
+    if let Some(Command::Plan { hours }) = opt.command {
+        if opt.at.is_some() || opt.conditions.temperature.is_some() || opt.conditions.stdin {
+            bail!(
+                "plan uses the forecast, so it can't be combined with --at, --temperature, or \
+                --stdin"
+            );
+        }
+        return plan(&opt, &config, &profile, &preferences, &catalog, hours);
+    }
 
-    let conditions = weather::get_current(&owm_api_key, &loc)?;
-    let preferences = UserPreferences::default();
-    let params = RunParameters::new(&conditions, &preferences);
+    let manual_air_quality = opt.conditions.to_air_quality();
+    let manual_conditions = opt.conditions.to_conditions()?;
+    // The forecast is also used to follow the weather over the run, when it's known how long the
+    // run lasts
+    let (conditions, run_time, air_quality, start, forecast) = match manual_conditions {
+        Some(conditions) => {
+            conditions.validate()?;
+            (conditions, None, manual_air_quality, Utc::now(), None)
+        }
+        None => {
+            let loc = resolve_location(&opt, &config, &profile)?;
+            let provider = get_provider(&opt, &config)?;
+            match &opt.at {
+                Some(when) => {
+                    let forecast = provider.forecast(&loc)?;
+                    let run_time = when.resolve(forecast.now_local())?;
+                    let start = forecast.to_utc(run_time);
+                    let conditions = forecast.at(start)?;
+                    // Air quality is only available for right now
+                    (
+                        conditions,
+                        Some(run_time),
+                        manual_air_quality,
+                        start,
+                        Some(forecast),
+                    )
+                }
+                None => {
+                    let conditions = provider.current(&loc)?;
+                    let air_quality = match manual_air_quality {
+                        Some(air_quality) => Some(air_quality),
+                        // Nice to know, but not worth failing over
+                        None => provider.air_quality(&loc).unwrap_or_else(|e| {
+                            eprintln!("Couldn't get the air quality: {}", e);
+                            None
+                        }),
+                    };
+                    let forecast = match preferences.duration {
+                        Some(_) => provider.forecast(&loc).map(Some).unwrap_or_else(|e| {
+                            eprintln!("Couldn't get the forecast for the rest of the run: {}", e);
+                            None
+                        }),
+                        None => None,
+                    };
+                    (conditions, None, air_quality, Utc::now(), forecast)
+                }
+            }
+        }
+    };
 
-    let outfit = Outfit::new(&params)?;
-    println!("\nParameters:\n{}\n\nOutfit:\n{}", params, outfit);
+    let age = freshness(&opt, &config).check(conditions.observed_at, Utc::now())?;
+    let params = RunParameters::new(&conditions, &preferences).with_air_quality(air_quality);
+    let (outfit, explanation) = match Outfit::explain(&params, &catalog) {
+        Ok(picked) => picked,
+        // Show why nothing fit before giving up
+        Err(picker::Error::InvalidOutfit { explanation, .. }) => {
+            match opt.format {
+                Format::Text => {
+                    eprintln!("Explanation:\n{}", explanation.in_units(preferences.units))
+                }
+                Format::Json => println!("{}", serde_json::to_string_pretty(&explanation)?),
+            }
+            let missing: Vec<String> = explanation
+                .missing()
+                .iter()
+                .map(|slot| format!("{:?}", slot).to_lowercase())
+                .collect();
+            bail!(
+                "Nothing in the catalog fits the {} in these conditions",
+                missing.join(" or ")
+            );
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let warnings = safety::assess(&params);
+    let trend = match (&forecast, preferences.duration) {
+        (Some(forecast), Some(duration)) => {
+            match Trend::new(&params, forecast, &catalog, start, duration) {
+                Ok(trend) => Some(trend),
+                Err(e) => {
+                    eprintln!("Couldn't follow the forecast over the run: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    let explain = matches!(opt.command, Some(Command::Explain));
+
+    match opt.format {
+        Format::Text => {
+            if let Some(run_time) = run_time {
+                println!(
+                    "\nForecast for {}",
+                    run_time.format("%A %b %-d at %-I:%M %p")
+                );
+            }
+            if let Some(age) = age {
+                println!("\nBased on weather from {}", weather::describe_age(age));
+            }
+            if !warnings.is_empty() {
+                println!("\nWarnings:");
+                for warning in &warnings {
+                    println!("{}", warning);
+                }
+            }
+            println!("\nParameters:\n{}", params);
+            match &trend {
+                Some(trend) => println!("\nOutfit:\n{}", trend),
+                None => println!("\nOutfit:\n{}", outfit),
+            }
+            if explain {
+                println!("Explanation:\n{}", explanation.in_units(preferences.units));
+            }
+        }
+        Format::Json => {
+            let report = Report {
+                run_time,
+                weather_age_minutes: age.map(|age| age.num_minutes()),
+                warnings: &warnings,
+                parameters: &params,
+                outfit: &outfit,
+                trend: trend.as_ref(),
+                comfort: conditions.comfort(),
+                uv_index: (conditions.estimated_uv_index() * 10.0).round() / 10.0,
+                uv_category: conditions.uv_category(),
+                explanation: if explain { Some(&explanation) } else { None },
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
     Ok(())
 }
 
+// Find the best time to run over the next `hours` hours
+fn plan(
+    opt: &Opt,
+    config: &Config,
+    profile: &Profile,
+    preferences: &UserPreferences,
+    catalog: &GearCatalog,
+    hours: u32,
+) -> Result<()> {
+    let loc = resolve_location(opt, config, profile)?;
+    let forecast = get_provider(opt, config)?.forecast(&loc)?;
+    let start = Utc::now();
+    freshness(opt, config).check(forecast.issued_at, start)?;
+    let end = start + Duration::hours(hours.into());
+    let plan = Plan::new(&forecast, preferences, catalog, start, end)?;
+    match opt.format {
+        Format::Text => print!("\n{}", plan.in_units(preferences.units)),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&plan)?),
+    }
+    Ok(())
+}
+
+fn freshness(opt: &Opt, config: &Config) -> Freshness {
+    let mut freshness = Freshness::default();
+    if let Some(minutes) = opt.max_age.or(config.max_weather_age_minutes) {
+        freshness.max_age = Duration::minutes(minutes);
+    }
+    freshness
+}
+
+fn get_provider(opt: &Opt, config: &Config) -> Result<Box<dyn WeatherProvider>> {
+    let settings = ProviderSettings {
+        owm_api_key: get_owm_key(config),
+        fixture: opt.fixture.clone().or_else(|| config.fixture.clone()),
+        cache: if opt.no_cache {
+            None
+        } else {
+            config.cache.settings()
+        },
+    };
+    let kind = opt.provider.or(config.provider).unwrap_or_default();
+    Ok(kind.build(&settings)?)
+}
+
 #[cfg(feature = "dotenv_key")]
-// Get OpenWeatherMap
-fn get_owm_key() -> Result<String> {
+// Get OpenWeatherMap API key from the environment or .env file, falling back to the config file
+fn get_owm_key(config: &Config) -> Option<String> {
     dotenv::dotenv().ok();
-    dotenv::var("OWM_API_KEY").map_err(|_| anyhow!("No OpenWeatherMap API key provided"))
+    dotenv::var("OWM_API_KEY")
+        .ok()
+        .or_else(|| config.owm_api_key.clone())
 }
 
 #[cfg(not(feature = "dotenv_key"))]
-fn get_owm_key() -> Result<String> {
-    std::env::var("OWM_API_KEY").map_err(|_| anyhow!("No OpenWeatherMap API key provided"))
+// Get OpenWeatherMap API key from the environment, falling back to the config file
+fn get_owm_key(config: &Config) -> Option<String> {
+    std::env::var("OWM_API_KEY")
+        .ok()
+        .or_else(|| config.owm_api_key.clone())
 }
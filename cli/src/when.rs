@@ -0,0 +1,190 @@
+//! Parsing for loosely specified run times like "tomorrow 6am" or "saturday morning"
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use picker::weather;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Day {
+    Today,
+    Tomorrow,
+    Weekday(Weekday),
+    Date(NaiveDate),
+}
+
+/// A run time relative to now, in the location's local time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct When {
+    day: Option<Day>,
+    time: Option<NaiveTime>,
+}
+
+impl When {
+    /// The next time matching this one, e.g. the next 6am. Fails if an explicit day and time, like
+    /// "today 6am", have already passed.
+    pub fn resolve(&self, now: NaiveDateTime) -> Result<NaiveDateTime> {
+        let today = now.date();
+        let date = self.day.map(|day| match day {
+            Day::Today => today,
+            Day::Tomorrow => today + Duration::days(1),
+            Day::Weekday(weekday) => {
+                let days_ahead = (7 + weekday.num_days_from_monday()
+                    - today.weekday().num_days_from_monday())
+                    % 7;
+                // Today's already gone by, so it must mean next week
+                let days_ahead = match weather::next_occurrence(now, Some(today), self.time) {
+                    Err(_) if days_ahead == 0 => 7,
+                    _ => days_ahead,
+                };
+                today + Duration::days(days_ahead.into())
+            }
+            Day::Date(date) => date,
+        });
+        Ok(weather::next_occurrence(now, date, self.time)?)
+    }
+}
+
+fn hm(hour: u32, minute: u32) -> Option<NaiveTime> {
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn parse_day(word: &str) -> Option<Day> {
+    match word {
+        "today" => Some(Day::Today),
+        "tomorrow" => Some(Day::Tomorrow),
+        _ => word.parse::<Weekday>().ok().map(Day::Weekday).or_else(|| {
+            NaiveDate::parse_from_str(word, "%Y-%m-%d")
+                .ok()
+                .map(Day::Date)
+        }),
+    }
+}
+
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    match word {
+        "morning" => return hm(7, 0),
+        "noon" => return hm(12, 0),
+        "afternoon" => return hm(14, 0),
+        "evening" => return hm(18, 0),
+        "night" => return hm(21, 0),
+        _ => (),
+    }
+    let (clock, pm) = if let Some(clock) = word.strip_suffix("am") {
+        (clock, Some(false))
+    } else if let Some(clock) = word.strip_suffix("pm") {
+        (clock, Some(true))
+    } else {
+        (word, None)
+    };
+    let mut parts = clock.splitn(2, ':');
+    let hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = match parts.next() {
+        Some(minute) => minute.parse().ok()?,
+        None if pm.is_some() => 0,
+        // Avoid treating bare numbers like "6" as times
+        None => return None,
+    };
+    let hour = match pm {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some(false) => hour % 12,
+        Some(true) => hour % 12 + 12,
+        None => hour,
+    };
+    hm(hour, minute)
+}
+
+impl FromStr for When {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "Couldn't understand '{}'. Try something like \"tomorrow 6am\".",
+                s
+            )
+        };
+        let lower = s.to_lowercase();
+        // Glue "6 am" back together so that it's a single word
+        let lower = lower.replace(" am", "am").replace(" pm", "pm");
+
+        let mut when = Self {
+            day: None,
+            time: None,
+        };
+        // "tonight" implies a time, but one given explicitly, as in "tonight 10pm", wins
+        let mut tonight = false;
+        for word in lower.split_whitespace() {
+            match word {
+                "at" | "this" | "on" | "in" | "the" => continue,
+                "tonight" if when.day.is_none() => {
+                    when.day = Some(Day::Today);
+                    tonight = true;
+                    continue;
+                }
+                _ => (),
+            }
+            if let (None, Some(day)) = (when.day, parse_day(word)) {
+                when.day = Some(day);
+            } else if let (None, Some(time)) = (when.time, parse_time(word)) {
+                when.time = Some(time);
+            } else {
+                return Err(invalid());
+            }
+        }
+        if tonight && when.time.is_none() {
+            when.time = parse_time("night");
+        }
+        if when.day.is_none() && when.time.is_none() {
+            return Err(invalid());
+        }
+        Ok(when)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_resolve() {
+        // A Sunday
+        let now = NaiveDate::from_ymd_opt(2020, 10, 18)
+            .unwrap()
+            .and_hms_opt(20, 0, 0)
+            .unwrap();
+        let resolve = |s: &str| s.parse::<When>().unwrap().resolve(now).unwrap().to_string();
+
+        assert_eq!("2020-10-19 06:00:00", resolve("tomorrow 6am"));
+        assert_eq!("2020-10-19 06:30:00", resolve("6:30 AM"));
+        assert_eq!("2020-10-18 21:00:00", resolve("tonight"));
+        assert_eq!("2020-10-19 18:00:00", resolve("at 18:00"));
+        assert_eq!("2020-10-24 07:00:00", resolve("saturday morning"));
+        assert_eq!("2020-10-18 20:30:00", resolve("this sunday 8:30pm"));
+        assert_eq!("2020-10-21 12:00:00", resolve("2020-10-21 noon"));
+        assert_eq!("2020-10-18 22:00:00", resolve("tonight 10pm"));
+        assert_eq!("2020-10-18 22:00:00", resolve("10pm tonight"));
+        // A weekday that's already gone by today means next week
+        assert_eq!("2020-10-25 19:00:00", resolve("sunday 7pm"));
+        assert_eq!("2020-10-25 07:00:00", resolve("sunday"));
+        // But today can't mean anything other than today
+        let past = |s: &str| {
+            s.parse::<When>()
+                .unwrap()
+                .resolve(now)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            "Sun Oct 18 at 7:00 AM has already passed. Try a later time.",
+            past("today")
+        );
+        assert_eq!(
+            "Sun Oct 18 at 7:00 PM has already passed. Try a later time.",
+            past("today 7pm")
+        );
+        assert!("tonight tomorrow".parse::<When>().is_err());
+        assert!("6".parse::<When>().is_err());
+        assert!("13pm".parse::<When>().is_err());
+        assert!("someday".parse::<When>().is_err());
+    }
+}
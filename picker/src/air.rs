@@ -0,0 +1,150 @@
+//! Air quality, and when it's bad enough to change running plans
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+// US EPA breakpoints for 24-hour PM2.5 in µg/m³, with the index range each maps to
+const PM2_5_BREAKPOINTS: [(f32, f32, u16, u16); 6] = [
+    (0.0, 9.0, 0, 50),
+    (9.1, 35.4, 51, 100),
+    (35.5, 55.4, 101, 150),
+    (55.5, 125.4, 151, 200),
+    (125.5, 225.4, 201, 300),
+    (225.5, 325.4, 301, 500),
+];
+
+/// Air quality at a location
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AirQuality {
+    /// US EPA [air quality index](https://www.airnow.gov/aqi/aqi-basics/)
+    pub aqi: u16,
+    /// Fine particulate matter in µg/m³, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pm2_5: Option<f32>,
+}
+
+impl AirQuality {
+    pub const fn from_aqi(aqi: u16) -> Self {
+        Self { aqi, pm2_5: None }
+    }
+
+    /// Air quality going by fine particulate matter in µg/m³, which is what wildfire smoke is
+    /// mostly made of
+    pub fn from_pm2_5(pm2_5: f32) -> Self {
+        // Concentrations are truncated to one decimal place before looking up the breakpoint
+        let concentration = (pm2_5.max(0.0) * 10.0).floor() / 10.0;
+        let aqi = PM2_5_BREAKPOINTS
+            .iter()
+            .find(|&&(_, high, _, _)| concentration <= high)
+            .map_or(500, |&(low, high, aqi_low, aqi_high)| {
+                let fraction = (concentration - low).max(0.0) / (high - low);
+                (f32::from(aqi_low) + fraction * f32::from(aqi_high - aqi_low)).round() as u16
+            });
+        Self {
+            aqi,
+            pm2_5: Some(pm2_5),
+        }
+    }
+
+    pub fn category(&self) -> AqiCategory {
+        AqiCategory::from_aqi(self.aqi)
+    }
+
+    /// What runners should do about the air quality, if anything
+    pub fn advisory(&self) -> Option<&'static str> {
+        match self.category() {
+            AqiCategory::Good => None,
+            AqiCategory::Moderate => Some(
+                "Air quality is moderate, so take it easy if you're unusually sensitive to air \
+                pollution.",
+            ),
+            AqiCategory::UnhealthyForSensitiveGroups => Some(
+                "Air quality is unhealthy for sensitive groups. If you have asthma or another lung \
+                or heart condition, consider a mask or a shorter, easier run.",
+            ),
+            AqiCategory::Unhealthy => Some(
+                "Air quality is unhealthy. Consider a well-fitted mask and a short, easy run, or \
+                move this run indoors.",
+            ),
+            AqiCategory::VeryUnhealthy => {
+                Some("Air quality is very unhealthy. Move this run indoors.")
+            }
+            AqiCategory::Hazardous => Some(
+                "Air quality is hazardous. Move this run indoors and keep time outside to a \
+                minimum.",
+            ),
+        }
+    }
+}
+
+impl fmt::Display for AirQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AQI {} ({})", self.aqi, self.category())?;
+        if let Some(pm2_5) = self.pm2_5 {
+            write!(f, ", PM2.5 {:.0} µg/m³", pm2_5)?;
+        }
+        Ok(())
+    }
+}
+
+/// How healthy the air is, from the US EPA's air quality index categories
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AqiCategory {
+    Good,
+    Moderate,
+    UnhealthyForSensitiveGroups,
+    Unhealthy,
+    VeryUnhealthy,
+    Hazardous,
+}
+
+impl AqiCategory {
+    pub const fn from_aqi(aqi: u16) -> Self {
+        match aqi {
+            0..=50 => Self::Good,
+            51..=100 => Self::Moderate,
+            101..=150 => Self::UnhealthyForSensitiveGroups,
+            151..=200 => Self::Unhealthy,
+            201..=300 => Self::VeryUnhealthy,
+            _ => Self::Hazardous,
+        }
+    }
+}
+
+impl fmt::Display for AqiCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Good => "good",
+            Self::Moderate => "moderate",
+            Self::UnhealthyForSensitiveGroups => "unhealthy for sensitive groups",
+            Self::Unhealthy => "unhealthy",
+            Self::VeryUnhealthy => "very unhealthy",
+            Self::Hazardous => "hazardous",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_pm2_5() {
+        assert_eq!(0, AirQuality::from_pm2_5(0.0).aqi);
+        assert_eq!(56, AirQuality::from_pm2_5(12.0).aqi);
+        assert_eq!(101, AirQuality::from_pm2_5(35.5).aqi);
+        // Between breakpoints after truncating
+        assert_eq!(100, AirQuality::from_pm2_5(35.45).aqi);
+        assert_eq!(500, AirQuality::from_pm2_5(400.0).aqi);
+
+        let smoky = AirQuality::from_pm2_5(150.0);
+        assert_eq!(AqiCategory::VeryUnhealthy, smoky.category());
+        assert_eq!(
+            "AQI 225 (very unhealthy), PM2.5 150 µg/m³",
+            smoky.to_string()
+        );
+        assert!(smoky.advisory().unwrap().contains("indoors"));
+        assert_eq!(None, AirQuality::from_aqi(30).advisory());
+    }
+}
@@ -1,18 +1,61 @@
-use crate::gear::Outfit;
+use crate::gear::{Explanation, Outfit};
 use crate::weather::Conditions;
+use chrono::Duration;
+use std::io;
 use std::result;
-use std::time::SystemTimeError;
 
 pub type Result<T> = result::Result<T, Error>;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("The given weather conditions are invalid")]
-    InvalidWeather(Conditions),
-    #[error("Failed to retrieve weather")]
-    FetchWeather(#[from] openweather::Error),
-    #[error("Problem with time calculation")]
-    SystemTimeError(#[from] SystemTimeError),
+    #[error("The given weather conditions are invalid: {reason}")]
+    InvalidWeather {
+        conditions: Conditions,
+        reason: String,
+    },
+    #[error("Failed to retrieve weather from {provider}")]
+    FetchWeather {
+        provider: &'static str,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Weather provider isn't configured: {0}")]
+    ConfigureProvider(String),
+    #[error(
+        "The latest weather is from {}, which is too old to use",
+        crate::weather::describe_age(*.age)
+    )]
+    StaleWeather { age: Duration },
+    /// Nothing in the catalog fits the torso, legs, or feet in these conditions. The
+    /// explanation says why each item was or wasn't picked.
     #[error("The generated outfit is invalid")]
-    InvalidOutfit(Outfit),
+    InvalidOutfit {
+        outfit: Box<Outfit>,
+        explanation: Box<Explanation>,
+    },
+    #[error("Failed to read gear catalog")]
+    ReadCatalog(#[source] io::Error),
+    #[error("Failed to parse gear catalog")]
+    ParseCatalog(#[from] toml::de::Error),
+    #[error("Failed to parse gear catalog")]
+    ParseCatalogJson(#[from] serde_json::Error),
+    #[error("Invalid gear catalog: {0}")]
+    InvalidCatalog(String),
+    #[error("Failed to read weather fixture")]
+    ReadFixture(#[source] io::Error),
+    #[error("Failed to parse weather fixture")]
+    ParseFixture(#[source] serde_json::Error),
+    #[error("{0}")]
+    InvalidInput(String),
+}
+
+impl Error {
+    pub(crate) fn fetch_weather(
+        provider: &'static str,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> Self {
+        Self::FetchWeather {
+            provider,
+            source: source.into(),
+        }
+    }
 }
@@ -1,241 +1,309 @@
 use crate::inputs::{Intensity, RunParameters, Sex};
-use crate::weather::{TimeOfDay, Weather};
+use crate::rules::Rule;
+use crate::units::{DisplayUnits, Temperature, Units};
 use crate::{Error, Result};
+use serde_derive::{Deserialize, Serialize};
 use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Built-in gear table, loaded when no other catalog is supplied
+const DEFAULT_CATALOG: &str = include_str!("../data/gear.toml");
+
+/// Part of the body that a piece of gear is worn on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Slot {
+    Head,
+    Torso,
+    Legs,
+    Feet,
+    Accessories,
+}
 
-#[derive(Default, Clone, Copy)]
-struct Gear {
-    name: &'static str,
-    max_temp: Option<i16>,
-    min_temp: Option<i16>,
-    // Function should return true if gear is acceptable
-    other_checks: Option<fn(&Self, &RunParameters) -> bool>,
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Gear {
+    pub name: String,
+    pub slot: Slot,
+    pub min_temp: Option<Temperature>,
+    pub max_temp: Option<Temperature>,
+    // Additional rule that must pass for the gear to be acceptable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<Rule>,
 }
 
 impl Gear {
-    fn is_wearable(&self, params: &RunParameters) -> bool {
-        if let (Some(max_temp), Some(min_temp)) = (self.max_temp, self.min_temp) {
-            debug_assert!(max_temp >= min_temp);
-        }
+    /// Decide whether this gear is appropriate under the given parameters, and if not, why not
+    fn check(&self, params: &RunParameters) -> Verdict {
         // Check if current temperature is within acceptable range for this gear
-        let effective_temperature = params.effective_temperature();
+        let temperature = params.effective_temperature();
         if let Some(max_temp) = self.max_temp {
-            if effective_temperature > max_temp {
-                return false;
+            if temperature > max_temp {
+                return Verdict::TooWarm {
+                    temperature,
+                    max_temp,
+                };
             }
         }
         if let Some(min_temp) = self.min_temp {
-            if effective_temperature < min_temp {
-                return false;
+            if temperature < min_temp {
+                return Verdict::TooCold {
+                    temperature,
+                    min_temp,
+                };
             }
         }
         // Apply optional additional checks
-        if let Some(other_checks) = self.other_checks {
-            return other_checks(self, params);
+        match self
+            .rule
+            .as_ref()
+            .and_then(|rule| rule.find_failure(params))
+        {
+            Some(failed) => Verdict::RuleFailed(failed.clone()),
+            None => Verdict::Picked,
         }
-        true
     }
 }
 
-// Gear options
-const WINTER_CAP: Gear = Gear {
-    name: "a winter cap",
-    min_temp: None,
-    max_temp: Some(38),
-    other_checks: Some(disallow_heavy_rain),
-};
-
-const HAT: Gear = Gear {
-    name: "a hat with visor",
-    min_temp: None,
-    max_temp: None,
-    other_checks: Some(require_rain),
-};
-
-const SUNGLASSES: Gear = Gear {
-    name: "sunglasses",
-    min_temp: None,
-    max_temp: None,
-    other_checks: Some(require_sun),
-};
-
-const HEAVY_JACKET: Gear = Gear {
-    name: "a heavy jacket",
-    min_temp: None,
-    max_temp: Some(20),
-    other_checks: None,
-};
-
-const LIGHT_JACKET: Gear = Gear {
-    name: "a light jacket",
-    min_temp: Some(21),
-    max_temp: Some(35),
-    other_checks: None,
-};
-
-const VEST: Gear = Gear {
-    name: "a vest",
-    min_temp: Some(36),
-    max_temp: Some(40),
-    other_checks: None,
-};
-
-const LONG_SLEEVE: Gear = Gear {
-    name: "a long-sleeved shirt",
-    min_temp: None,
-    max_temp: Some(54),
-    other_checks: None,
-};
-
-const SHORT_SLEEVE: Gear = Gear {
-    name: "a short-sleeved shirt",
-    min_temp: Some(55),
-    max_temp: Some(65),
-    other_checks: None,
-};
-
-const SINGLET: Gear = Gear {
-    name: "a sleeveless shirt",
-    min_temp: Some(66),
-    max_temp: Some(85),
-    other_checks: Some(check_lower_heat_threshold_for_males),
-};
-
-const SPORTS_BRA: Gear = Gear {
-    name: "a sports bra",
-    min_temp: None,
-    max_temp: None,
-    other_checks: Some(require_female),
-};
-
-const TOPLESS: Gear = Gear {
-    name: "no shirt",
-    min_temp: Some(81),
-    max_temp: None,
-    other_checks: Some(require_male),
-};
-
-const TIGHTS: Gear = Gear {
-    name: "tights",
-    min_temp: None,
-    max_temp: Some(40),
-    other_checks: None,
-};
-
-const CAPRIS: Gear = Gear {
-    name: "capri tights",
-    min_temp: Some(41),
-    max_temp: Some(50),
-    other_checks: Some(require_female),
-};
-
-const SHORTS: Gear = Gear {
-    name: "shorts",
-    min_temp: Some(40),
-    max_temp: None,
-    other_checks: None,
-};
-
-const GLOVES: Gear = Gear {
-    name: "gloves",
-    min_temp: None,
-    max_temp: Some(47),
-    other_checks: Some(disallow_races),
-};
-
-const SHOES: Gear = Gear {
-    name: "running shoes",
-    min_temp: None,
-    max_temp: None,
-    other_checks: None,
-};
-
-const SUNBLOCK: Gear = Gear {
-    name: "sunblock",
-    min_temp: None,
-    max_temp: None,
-    other_checks: Some(require_bright_sun),
-};
-
-// Extra checks that can be used for Gear::other_checks field
-
-fn check_lower_heat_threshold_for_males(_gear: &Gear, params: &RunParameters) -> bool {
-    match params.preferences.sex {
-        Sex::Male => params.effective_temperature() <= 80,
-        Sex::Female => true,
+impl DisplayUnits for Gear {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result {
+        write!(f, "{} ({:?})", self.name, self.slot)?;
+        match (self.min_temp, self.max_temp) {
+            (Some(min_temp), Some(max_temp)) => write!(
+                f,
+                ": {} to {}",
+                min_temp.in_units(units),
+                max_temp.in_units(units)
+            )?,
+            (Some(min_temp), None) => write!(f, ": {} and up", min_temp.in_units(units))?,
+            (None, Some(max_temp)) => write!(f, ": up to {}", max_temp.in_units(units))?,
+            (None, None) => write!(f, ": any temperature")?,
+        }
+        if let Some(rule) = &self.rule {
+            write!(f, ", when {}", rule.in_units(units))?;
+        }
+        Ok(())
     }
 }
 
-fn disallow_heavy_rain(_gear: &Gear, params: &RunParameters) -> bool {
-    match params.conditions.weather {
-        Weather::HeavyRain => false,
-        _ => true,
+impl fmt::Display for Gear {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_units(f, Units::default())
     }
 }
 
-fn require_rain(_gear: &Gear, params: &RunParameters) -> bool {
-    match params.conditions.weather {
-        Weather::HeavyRain | Weather::Rain => true,
-        _ => false,
+/// Collection of gear that an outfit is picked from
+///
+/// The built-in catalog is available via [`GearCatalog::default`]. Custom catalogs can be loaded
+/// from TOML or JSON with the same layout as `data/gear.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GearCatalog {
+    /// Name of a torso item that replaces all other torso items when a male runner is racing
+    pub race_top: Option<String>,
+    pub gear: Vec<Gear>,
+}
+
+impl Default for GearCatalog {
+    fn default() -> Self {
+        Self::from_toml(DEFAULT_CATALOG).expect("Built-in gear catalog is invalid")
     }
 }
 
-fn require_sun(_gear: &Gear, params: &RunParameters) -> bool {
-    if let TimeOfDay::Night = params.conditions.time {
-        return false;
+impl GearCatalog {
+    pub fn from_toml(s: &str) -> Result<Self> {
+        let catalog: Self = toml::from_str(s)?;
+        catalog.validate()?;
+        Ok(catalog)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self> {
+        let catalog: Self = serde_json::from_str(s)?;
+        catalog.validate()?;
+        Ok(catalog)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| Error::InvalidCatalog(e.to_string()))
+    }
+
+    /// Load a catalog from a file. Files with a `.json` extension are parsed as JSON and all
+    /// others as TOML.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(Error::ReadCatalog)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json(&contents),
+            _ => Self::from_toml(&contents),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        for gear in &self.gear {
+            if let (Some(min_temp), Some(max_temp)) = (gear.min_temp, gear.max_temp) {
+                if min_temp > max_temp {
+                    return Err(Error::InvalidCatalog(format!(
+                        "{} has a minimum temperature above its maximum temperature",
+                        gear.name
+                    )));
+                }
+            }
+        }
+        if let Some(race_top) = &self.race_top {
+            if !self
+                .gear
+                .iter()
+                .any(|gear| gear.slot == Slot::Torso && &gear.name == race_top)
+            {
+                return Err(Error::InvalidCatalog(format!(
+                    "race top {} is not a torso item in the catalog",
+                    race_top
+                )));
+            }
+        }
+        Ok(())
     }
-    match params.conditions.weather {
-        Weather::Clear | Weather::PartlyCloudy => true,
-        _ => false,
+
+    pub fn slot(&self, slot: Slot) -> impl Iterator<Item = &Gear> {
+        self.gear.iter().filter(move |gear| gear.slot == slot)
     }
 }
 
-fn require_male(_gear: &Gear, params: &RunParameters) -> bool {
-    match params.preferences.sex {
-        Sex::Male => true,
-        Sex::Female => false,
+/// Reason a piece of gear was or wasn't picked
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Picked,
+    TooWarm {
+        temperature: Temperature,
+        max_temp: Temperature,
+    },
+    TooCold {
+        temperature: Temperature,
+        min_temp: Temperature,
+    },
+    /// The gear's rule failed. Holds the most specific part of the rule that failed.
+    RuleFailed(Rule),
+    /// Picked as the race-day top, regardless of its own limits
+    RaceTop,
+    /// Would have been picked, but the given race-day top was picked instead
+    ReplacedByRaceTop(String),
+}
+
+impl Verdict {
+    pub const fn is_picked(&self) -> bool {
+        matches!(self, Self::Picked | Self::RaceTop)
     }
 }
 
-fn require_female(_gear: &Gear, params: &RunParameters) -> bool {
-    match params.preferences.sex {
-        Sex::Male => false,
-        Sex::Female => true,
+#[derive(Debug, Clone, Serialize)]
+pub struct Decision {
+    pub name: String,
+    pub slot: Slot,
+    pub verdict: Verdict,
+}
+
+impl DisplayUnits for Decision {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result {
+        match &self.verdict {
+            Verdict::Picked => write!(f, "✔ {}: picked", self.name),
+            Verdict::TooWarm {
+                temperature,
+                max_temp,
+            } => write!(
+                f,
+                "✘ {}: {} is above its {} maximum",
+                self.name,
+                temperature.in_units(units),
+                max_temp.in_units(units)
+            ),
+            Verdict::TooCold {
+                temperature,
+                min_temp,
+            } => write!(
+                f,
+                "✘ {}: {} is below its {} minimum",
+                self.name,
+                temperature.in_units(units),
+                min_temp.in_units(units)
+            ),
+            Verdict::RuleFailed(rule) => {
+                write!(f, "✘ {}: requires {}", self.name, rule.in_units(units))
+            }
+            Verdict::RaceTop => write!(f, "✔ {}: picked for race day", self.name),
+            Verdict::ReplacedByRaceTop(race_top) => {
+                write!(f, "✘ {}: replaced by {} for race day", self.name, race_top)
+            }
+        }
     }
 }
 
-fn disallow_races(_gear: &Gear, params: &RunParameters) -> bool {
-    match params.preferences.intensity {
-        Intensity::Race => false,
-        _ => true,
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_units(f, Units::default())
     }
 }
 
-fn require_bright_sun(_gear: &Gear, params: &RunParameters) -> bool {
-    if let TimeOfDay::Night = params.conditions.time {
-        return false;
+/// Trace of how each item in a catalog was judged when picking an outfit
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    pub effective_temperature: Temperature,
+    pub decisions: Vec<Decision>,
+}
+
+impl Explanation {
+    /// Find decisions for gear whose name contains the given text, e.g. "gloves"
+    pub fn find(&self, query: &str) -> Vec<&Decision> {
+        let query = query.to_lowercase();
+        self.decisions
+            .iter()
+            .filter(|decision| decision.name.to_lowercase().contains(&query))
+            .collect()
     }
-    match params.conditions.weather {
-        Weather::Clear | Weather::PartlyCloudy => true,
-        _ => false,
+
+    /// Slots that every outfit needs but that nothing was picked for
+    pub fn missing(&self) -> Vec<Slot> {
+        [Slot::Torso, Slot::Legs, Slot::Feet]
+            .iter()
+            .copied()
+            .filter(|&slot| self.picked(slot).is_empty())
+            .collect()
+    }
+
+    fn picked(&self, slot: Slot) -> Vec<String> {
+        self.decisions
+            .iter()
+            .filter(|decision| decision.slot == slot && decision.verdict.is_picked())
+            .map(|decision| decision.name.clone())
+            .collect()
     }
 }
 
-fn filter_wearable<'a>(choices: &[&'a Gear], params: &RunParameters) -> Vec<&'a str> {
-    choices
-        .iter()
-        .filter(|x| x.is_wearable(params))
-        .map(|x| x.name)
-        .collect()
+impl DisplayUnits for Explanation {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result {
+        writeln!(
+            f,
+            "Effective temperature: {}",
+            self.effective_temperature.in_units(units)
+        )?;
+        for decision in &self.decisions {
+            writeln!(f, "{}", decision.in_units(units))?;
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug)]
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_units(f, Units::default())
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct Outfit {
-    pub head: Vec<&'static str>,
-    pub torso: Vec<&'static str>,
-    pub legs: Vec<&'static str>,
-    pub feet: Vec<&'static str>,
-    pub accessories: Vec<&'static str>,
+    pub head: Vec<String>,
+    pub torso: Vec<String>,
+    pub legs: Vec<String>,
+    pub feet: Vec<String>,
+    pub accessories: Vec<String>,
 }
 
 impl fmt::Display for Outfit {
@@ -260,43 +328,186 @@ impl fmt::Display for Outfit {
 }
 
 impl Outfit {
-    pub fn new(params: &RunParameters) -> Result<Self> {
-        let head_options = vec![&WINTER_CAP, &HAT];
-        let torso_options = vec![
-            &HEAVY_JACKET,
-            &LIGHT_JACKET,
-            &VEST,
-            &LONG_SLEEVE,
-            &SHORT_SLEEVE,
-            &SINGLET,
-            &SPORTS_BRA,
-            &TOPLESS,
-        ];
-        let legs_options = vec![&TIGHTS, &CAPRIS, &SHORTS];
-        let feet_options = vec![&SHOES];
-        let accessories_options = vec![&GLOVES, &SUNGLASSES, &SUNBLOCK];
-
-        let mut outfit = Self {
-            head: filter_wearable(&head_options, params),
-            torso: filter_wearable(&torso_options, params),
-            legs: filter_wearable(&legs_options, params),
-            feet: filter_wearable(&feet_options, params),
-            accessories: filter_wearable(&accessories_options, params),
+    pub fn new(params: &RunParameters, catalog: &GearCatalog) -> Result<Self> {
+        Self::explain(params, catalog).map(|(outfit, _)| outfit)
+    }
+
+    /// Pick an outfit, also returning an explanation of why each item was or wasn't picked
+    pub fn explain(params: &RunParameters, catalog: &GearCatalog) -> Result<(Self, Explanation)> {
+        let mut explanation = Explanation {
+            effective_temperature: params.effective_temperature(),
+            decisions: catalog
+                .gear
+                .iter()
+                .map(|gear| Decision {
+                    name: gear.name.clone(),
+                    slot: gear.slot,
+                    verdict: gear.check(params),
+                })
+                .collect(),
         };
 
         // Special override for males running races
         if let Sex::Male = params.preferences.sex {
             if let Intensity::Race = params.preferences.intensity {
-                if params.effective_temperature() > 35 {
-                    outfit.torso = vec![SINGLET.name];
+                if params.effective_temperature().fahrenheit() > 35.0 {
+                    if let Some(race_top) = &catalog.race_top {
+                        for decision in explanation
+                            .decisions
+                            .iter_mut()
+                            .filter(|decision| decision.slot == Slot::Torso)
+                        {
+                            if &decision.name == race_top {
+                                decision.verdict = Verdict::RaceTop;
+                            } else if decision.verdict.is_picked() {
+                                decision.verdict = Verdict::ReplacedByRaceTop(race_top.clone());
+                            }
+                        }
+                    }
                 }
             }
         }
 
+        let outfit = Self {
+            head: explanation.picked(Slot::Head),
+            torso: explanation.picked(Slot::Torso),
+            legs: explanation.picked(Slot::Legs),
+            feet: explanation.picked(Slot::Feet),
+            accessories: explanation.picked(Slot::Accessories),
+        };
+
         if outfit.torso.is_empty() || outfit.legs.is_empty() || outfit.feet.is_empty() {
-            Err(Error::InvalidOutfit(outfit))
+            Err(Error::InvalidOutfit {
+                outfit: Box::new(outfit),
+                explanation: Box::new(explanation),
+            })
         } else {
-            Ok(outfit)
+            Ok((outfit, explanation))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inputs::UserPreferences;
+    use crate::weather::{Conditions, TimeOfDay, Weather};
+
+    // Outfit from the built-in catalog at 45°F, at the given time and in the given weather
+    fn outfit_at(time: TimeOfDay, weather: Weather) -> Outfit {
+        let conditions = Conditions {
+            temperature: Temperature::from_fahrenheit(45.0),
+            weather,
+            time,
+            ..Conditions::default()
+        };
+        let params = RunParameters::new(&conditions, &UserPreferences::default());
+        Outfit::new(&params, &GearCatalog::default()).unwrap()
+    }
+
+    #[test]
+    fn test_default_catalog() {
+        let defaults = Conditions::default();
+        let outfit = outfit_at(defaults.time, defaults.weather);
+        assert_eq!(outfit.torso, vec!["a short-sleeved shirt"]);
+        assert_eq!(outfit.legs, vec!["shorts"]);
+    }
+
+    #[test]
+    fn test_visibility_gear() {
+        let night = outfit_at(TimeOfDay::Night, Weather::Clear);
+        assert!(night.head.contains(&"a headlamp".to_string()));
+        assert_eq!(
+            night.accessories[..2],
+            ["a reflective vest or bands", "a blinking clip light"]
+        );
+        // Dawn and dusk, whether or not the sun is up yet
+        let morning = outfit_at(TimeOfDay::Morning, Weather::Clear);
+        assert!(morning.head.is_empty());
+        assert!(morning
+            .accessories
+            .contains(&"a blinking clip light".to_string()));
+        let foggy_day = outfit_at(TimeOfDay::Daytime, Weather::Fog);
+        assert!(foggy_day.head.is_empty());
+        assert!(foggy_day
+            .accessories
+            .contains(&"a reflective vest or bands".to_string()));
+        assert!(outfit_at(TimeOfDay::Daytime, Weather::Clear)
+            .accessories
+            .iter()
+            .all(|item| !item.contains("reflective")));
+    }
+
+    #[test]
+    fn test_catalog_round_trip() {
+        let catalog = GearCatalog::default();
+        let reloaded = GearCatalog::from_toml(&catalog.to_toml().unwrap()).unwrap();
+        assert_eq!(catalog.gear.len(), reloaded.gear.len());
+        let rules = |catalog: &GearCatalog| -> Vec<Option<Rule>> {
+            catalog.gear.iter().map(|gear| gear.rule.clone()).collect()
+        };
+        assert_eq!(rules(&catalog), rules(&reloaded));
+        let json = serde_json::to_string(&catalog).unwrap();
+        assert_eq!(
+            catalog.gear.len(),
+            GearCatalog::from_json(&json).unwrap().gear.len()
+        );
+    }
+
+    #[test]
+    fn test_invalid_catalog() {
+        let toml = r#"
+            [[gear]]
+            name = "backwards"
+            slot = "torso"
+            min_temp = "10C"
+            max_temp = 40
+        "#;
+        assert!(matches!(
+            GearCatalog::from_toml(toml),
+            Err(Error::InvalidCatalog(_))
+        ));
+    }
+
+    #[test]
+    fn test_explain() {
+        let catalog = GearCatalog::default();
+        let conditions = Conditions {
+            temperature: Temperature::from_fahrenheit(15.0),
+            ..Conditions::default()
+        };
+        let preferences = UserPreferences {
+            intensity: Intensity::Race,
+            ..UserPreferences::default()
+        };
+        let params = RunParameters::new(&conditions, &preferences);
+        let (outfit, explanation) = Outfit::explain(&params, &catalog).unwrap();
+        assert_eq!(outfit.torso, vec!["a sleeveless shirt"]);
+
+        let gloves = explanation.find("gloves");
+        assert_eq!(gloves.len(), 1);
+        assert!(matches!(gloves[0].verdict, Verdict::RuleFailed(_)));
+        let long_sleeve = explanation.find("long-sleeved");
+        assert_eq!(
+            long_sleeve[0].verdict,
+            Verdict::ReplacedByRaceTop("a sleeveless shirt".to_string())
+        );
+
+        // A catalog without any shoes still explains what it picked
+        let catalog = GearCatalog {
+            race_top: catalog.race_top.clone(),
+            gear: catalog
+                .gear
+                .into_iter()
+                .filter(|gear| gear.slot != Slot::Feet)
+                .collect(),
+        };
+        match Outfit::explain(&params, &catalog) {
+            Err(Error::InvalidOutfit { explanation, .. }) => {
+                assert_eq!(explanation.missing(), vec![Slot::Feet]);
+                assert_eq!(explanation.find("sleeveless")[0].verdict, Verdict::RaceTop);
+            }
+            other => panic!("Expected an invalid outfit, got {:?}", other),
         }
     }
 }
@@ -0,0 +1,120 @@
+//! Humidity, dew point, and how they affect how warm it feels
+use crate::units::Temperature;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+// Constants for the Magnus approximation of saturation vapor pressure, for °C
+const MAGNUS_B: f32 = 17.625;
+const MAGNUS_C: f32 = 243.04;
+
+/// Temperature at or above which humidity starts to make it feel warmer
+pub const HEAT_INDEX_MIN: Temperature = Temperature::from_fahrenheit(80.0);
+
+/// Dew point for the given temperature and relative humidity (in percent)
+pub fn dew_point(temperature: Temperature, humidity: f32) -> Temperature {
+    let t = temperature.celsius();
+    let gamma = (humidity.max(1.0) / 100.0).ln() + MAGNUS_B * t / (MAGNUS_C + t);
+    Temperature::from_celsius(MAGNUS_C * gamma / (MAGNUS_B - gamma))
+}
+
+/// Relative humidity (in percent) for the given temperature and dew point
+pub fn relative_humidity(temperature: Temperature, dew_point: Temperature) -> f32 {
+    let (t, td) = (temperature.celsius(), dew_point.celsius());
+    let humidity = 100.0 * (MAGNUS_B * td / (MAGNUS_C + td) - MAGNUS_B * t / (MAGNUS_C + t)).exp();
+    humidity.min(100.0)
+}
+
+/// How hot it feels given the temperature and relative humidity (in percent), using the
+/// [NWS heat index](https://www.wpc.ncep.noaa.gov/html/heatindex_equation.shtml)
+pub fn heat_index(temperature: Temperature, humidity: f32) -> Temperature {
+    let (t, rh) = (temperature.fahrenheit(), humidity);
+    // The simple formula is good enough when it's not that hot
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    if (simple + t) / 2.0 < HEAT_INDEX_MIN.fahrenheit() {
+        return Temperature::from_fahrenheit(simple);
+    }
+    let mut index = -42.379 + 2.049_015_2 * t + 10.143_331 * rh
+        - 0.224_755_4 * t * rh
+        - 0.006_837_83 * t * t
+        - 0.054_817_17 * rh * rh
+        + 0.001_228_74 * t * t * rh
+        + 0.000_852_82 * t * rh * rh
+        - 0.000_001_99 * t * t * rh * rh;
+    if rh < 13.0 && (80.0..=112.0).contains(&t) {
+        index -= (13.0 - rh) / 4.0 * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+    } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+        index += (rh - 85.0) / 10.0 * (87.0 - t) / 5.0;
+    }
+    Temperature::from_fahrenheit(index)
+}
+
+/// How the air feels based on its dew point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comfort {
+    Dry,
+    Comfortable,
+    Sticky,
+    Humid,
+    Oppressive,
+    Miserable,
+}
+
+impl Comfort {
+    pub fn from_dew_point(dew_point: Temperature) -> Self {
+        let fahrenheit = dew_point.fahrenheit();
+        if fahrenheit < 50.0 {
+            Self::Dry
+        } else if fahrenheit < 60.0 {
+            Self::Comfortable
+        } else if fahrenheit < 65.0 {
+            Self::Sticky
+        } else if fahrenheit < 70.0 {
+            Self::Humid
+        } else if fahrenheit < 75.0 {
+            Self::Oppressive
+        } else {
+            Self::Miserable
+        }
+    }
+}
+
+impl fmt::Display for Comfort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Dry => "dry",
+            Self::Comfortable => "comfortable",
+            Self::Sticky => "sticky",
+            Self::Humid => "humid",
+            Self::Oppressive => "oppressive",
+            Self::Miserable => "miserable",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heat_index() {
+        // Values from the NWS heat index chart
+        let index = |fahrenheit, humidity| {
+            heat_index(Temperature::from_fahrenheit(fahrenheit), humidity)
+                .fahrenheit()
+                .round()
+        };
+        assert_eq!(91.0, index(86.0, 60.0));
+        assert_eq!(121.0, index(96.0, 65.0));
+        assert_eq!(80.0, index(80.0, 40.0));
+
+        let dew_point = dew_point(Temperature::from_fahrenheit(86.0), 60.0);
+        assert_eq!(71.0, dew_point.fahrenheit().round());
+        assert_eq!(
+            60.0,
+            relative_humidity(Temperature::from_fahrenheit(86.0), dew_point).round()
+        );
+        assert_eq!(Comfort::Oppressive, Comfort::from_dew_point(dew_point));
+    }
+}
@@ -1,7 +1,40 @@
-use crate::weather::{Conditions, TimeOfDay, Weather, Wind};
+use crate::air::AirQuality;
+use crate::humidity::{heat_index, HEAT_INDEX_MIN};
+use crate::sun;
+use crate::units::{DisplayUnits, RunDuration, Speed, Temperature, Units};
+use crate::weather::{wind_chill, Conditions, TimeOfDay, Weather};
+use crate::{Error, Result};
+use serde_derive::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+// Case-insensitively match `s` against a list of named choices, ignoring separators so that e.g.
+// "long-run", "long_run", and "LongRun" are all equivalent
+pub(crate) fn parse_choice<T: Copy>(kind: &str, s: &str, choices: &[(&str, T)]) -> Result<T> {
+    let normalize = |s: &str| -> String {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let normalized = normalize(s);
+    choices
+        .iter()
+        .find(|(name, _)| normalize(name) == normalized)
+        .map(|(_, choice)| *choice)
+        .ok_or_else(|| {
+            let names: Vec<&str> = choices.iter().map(|(name, _)| *name).collect();
+            Error::InvalidInput(format!(
+                "'{}' is not a valid {}. Expected one of: {}",
+                s,
+                kind,
+                names.join(", ")
+            ))
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Sex {
     Male,
     Female,
@@ -14,7 +47,26 @@ impl Default for Sex {
     }
 }
 
-#[derive(Debug, Clone)]
+impl fmt::Display for Sex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Male => "male",
+            Self::Female => "female",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Sex {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_choice("sex", s, &[("male", Self::Male), ("female", Self::Female)])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Intensity {
     LongRun,
     Average,
@@ -28,7 +80,37 @@ impl Default for Intensity {
     }
 }
 
-#[derive(Debug, Clone)]
+impl fmt::Display for Intensity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::LongRun => "long run",
+            Self::Average => "average",
+            Self::Workout => "workout",
+            Self::Race => "race",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Intensity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_choice(
+            "intensity",
+            s,
+            &[
+                ("long-run", Self::LongRun),
+                ("average", Self::Average),
+                ("workout", Self::Workout),
+                ("race", Self::Race),
+            ],
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Feel {
     RunsWarm,
     Average,
@@ -41,73 +123,161 @@ impl Default for Feel {
     }
 }
 
-#[derive(Default, Debug, Clone)]
+impl fmt::Display for Feel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::RunsWarm => "runs warm",
+            Self::Average => "average",
+            Self::RunsCold => "runs cold",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Feel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_choice(
+            "feel",
+            s,
+            &[
+                ("runs-warm", Self::RunsWarm),
+                ("average", Self::Average),
+                ("runs-cold", Self::RunsCold),
+            ],
+        )
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct UserPreferences {
     pub sex: Sex,
     pub intensity: Intensity,
     pub feel: Feel,
+    /// Units to display temperatures and speeds in
+    pub units: Units,
+    /// How long the run will last, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<RunDuration>,
 }
 
 impl fmt::Display for UserPreferences {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?} running @ {:?} intensity", self.sex, self.intensity)?;
+        if let Some(duration) = self.duration {
+            write!(f, " for {}", duration)?;
+        }
         match &self.feel {
             Feel::Average => Ok(()),
-            _ => write!(f, "{:?}", self.feel),
+            _ => write!(f, ", {:?}", self.feel),
         }
     }
 }
 
-#[derive(Debug)]
-pub struct RunParameters {
-    pub conditions: Conditions,
-    pub preferences: UserPreferences,
-    effective_temperature: i16,
+// Warming from the sun, which is only at full strength when it's high in the sky. Without the sun's
+// position, go by the time of day.
+fn sun_adjustment(conditions: &Conditions, full_sun: i16) -> i16 {
+    match conditions.sun_elevation {
+        Some(elevation) => (f32::from(full_sun) * sun::intensity(elevation)).round() as i16,
+        None => match conditions.time {
+            TimeOfDay::Daytime => full_sun,
+            TimeOfDay::Morning | TimeOfDay::Evening => full_sun / 2,
+            TimeOfDay::Night => 0,
+        },
+    }
 }
 
-impl RunParameters {
-    pub fn new(conditions: &Conditions, preferences: &UserPreferences) -> Self {
-        let effective_temperature = Self::calculate_effective_temperature(conditions, preferences);
-        Self {
-            conditions: conditions.clone(),
-            preferences: preferences.clone(),
-            effective_temperature,
+// Below 50°F, wind feels as cold as the wind chill says. The effect fades out from there up to 80°F,
+// where a breeze no longer makes it feel any colder.
+fn wind_adjustment(temperature: Temperature, speed: Speed) -> i16 {
+    const FULL_CHILL: f32 = 50.0;
+    const NO_CHILL: f32 = 80.0;
+    let chill_at = |fahrenheit: f32| {
+        wind_chill(Temperature::from_fahrenheit(fahrenheit), speed).fahrenheit() - fahrenheit
+    };
+    let fahrenheit = temperature.fahrenheit();
+    let adjustment = if fahrenheit <= FULL_CHILL {
+        chill_at(fahrenheit)
+    } else if fahrenheit < NO_CHILL {
+        chill_at(FULL_CHILL) * (NO_CHILL - fahrenheit) / (NO_CHILL - FULL_CHILL)
+    } else {
+        0.0
+    };
+    adjustment.round() as i16
+}
+
+// Runs shorter than this many minutes are over before the runner has fully warmed up
+const SHORT_RUN_MINUTES: u16 = 30;
+// In the cold, runs at least this many minutes long get chilly as the runner tires and their sweat
+// cools them off
+const LONG_RUN_MINUTES: u16 = 90;
+const LONG_RUN_CHILL: f32 = 50.0;
+
+fn duration_adjustment(temperature: Temperature, duration: Option<RunDuration>) -> i16 {
+    match duration.map(RunDuration::minutes) {
+        Some(minutes) if minutes < SHORT_RUN_MINUTES => -5,
+        Some(minutes)
+            if minutes >= LONG_RUN_MINUTES && temperature.fahrenheit() <= LONG_RUN_CHILL =>
+        {
+            -3
         }
+        _ => 0,
     }
+}
 
-    pub const fn effective_temperature(&self) -> i16 {
-        self.effective_temperature
-    }
+/// Breakdown of the temperature that a runner should dress for
+///
+/// Adjustments are in °F. The effective temperature is the sum of the actual temperature and each
+/// of the adjustments.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EffectiveTemperature {
+    /// Actual air temperature
+    pub base: Temperature,
+    /// Adjustment for sun or precipitation
+    pub weather_adj: i16,
+    /// Adjustment for humidity making heat feel worse
+    #[serde(default)]
+    pub humidity_adj: i16,
+    pub wind_adj: i16,
+    pub intensity_adj: i16,
+    /// Adjustment for how long the run lasts
+    #[serde(default)]
+    pub duration_adj: i16,
+    /// Adjustment for whether the runner tends to run warm or cold
+    pub user_adj: i16,
+}
 
-    fn calculate_effective_temperature(
-        conditions: &Conditions,
-        preferences: &UserPreferences,
-    ) -> i16 {
+impl EffectiveTemperature {
+    pub fn new(conditions: &Conditions, preferences: &UserPreferences) -> Self {
         // Adjust for weather conditions
         let weather_adj = match conditions.weather {
             Weather::Snow => -3,
+            Weather::Drizzle => -2,
             Weather::Rain => -4,
-            Weather::HeavyRain => -10,
-            Weather::Overcast => 0,
-            Weather::PartlyCloudy => match conditions.time {
-                TimeOfDay::Daytime => 5,
-                TimeOfDay::Morning | TimeOfDay::Evening => 2,
-                TimeOfDay::Night => 0,
-            },
-            Weather::Clear => match conditions.time {
-                TimeOfDay::Daytime => 10,
-                TimeOfDay::Morning | TimeOfDay::Evening => 5,
-                TimeOfDay::Night => 0,
-            },
+            Weather::Sleet => -7,
+            Weather::HeavyRain | Weather::Thunderstorm | Weather::FreezingRain => -10,
+            Weather::Overcast | Weather::Fog => 0,
+            // Haze lets about as much sun through as scattered clouds
+            Weather::PartlyCloudy | Weather::Haze => sun_adjustment(conditions, 5),
+            Weather::Clear => sun_adjustment(conditions, 10),
         };
 
-        // Adjust for wind
-        let wind_adj = match conditions.wind {
-            Wind::Light => -5,
-            Wind::Heavy => -9,
-            Wind::Calm => 0,
+        // Adjust for humidity, which only matters once it's hot
+        let humidity_adj = match conditions.relative_humidity() {
+            Some(humidity) if conditions.temperature >= HEAT_INDEX_MIN => {
+                let heat_index = heat_index(conditions.temperature, humidity);
+                (heat_index.fahrenheit() - conditions.temperature.fahrenheit())
+                    .round()
+                    .max(0.0) as i16
+            }
+            _ => 0,
         };
 
+        // Adjust for wind
+        let wind_adj = wind_adjustment(conditions.temperature, conditions.estimated_wind_speed());
+
         // Adjust for intensity
         let intensity_adj = match preferences.intensity {
             Intensity::Race => 15,
@@ -116,6 +286,9 @@ impl RunParameters {
             Intensity::Average => 0,
         };
 
+        // Adjust for how long the runner is out in the cold
+        let duration_adj = duration_adjustment(conditions.temperature, preferences.duration);
+
         // Adjust for user preference
         let user_adj = match preferences.feel {
             Feel::RunsWarm => 10,
@@ -123,12 +296,252 @@ impl RunParameters {
             Feel::Average => 0,
         };
 
-        conditions.temperature + weather_adj + wind_adj + intensity_adj + user_adj
+        Self {
+            base: conditions.temperature,
+            weather_adj,
+            humidity_adj,
+            wind_adj,
+            intensity_adj,
+            duration_adj,
+            user_adj,
+        }
+    }
+
+    /// Temperature to dress for
+    pub fn total(&self) -> Temperature {
+        self.adjusted(
+            self.feels_like_adj() + self.intensity_adj + self.duration_adj + self.user_adj,
+        )
+    }
+
+    /// Temperature adjusted for weather, humidity, and wind only, independent of the runner
+    pub fn feels_like(&self) -> Temperature {
+        self.adjusted(self.feels_like_adj())
+    }
+
+    fn feels_like_adj(&self) -> i16 {
+        self.weather_adj + self.humidity_adj + self.wind_adj
+    }
+
+    // Apply an adjustment to the base temperature, rounded to whole °F like the gear thresholds
+    fn adjusted(&self, adjustment: i16) -> Temperature {
+        Temperature::from_fahrenheit(self.base.fahrenheit().round() + f32::from(adjustment))
+    }
+
+    /// Names of the weather-related factors affecting the temperature, e.g. "wind"
+    pub fn weather_reasons(&self) -> Vec<&'static str> {
+        let mut reasons = Vec::new();
+        if self.weather_adj > 0 {
+            reasons.push("sun");
+        } else if self.weather_adj < 0 {
+            reasons.push("precipitation");
+        }
+        if self.humidity_adj != 0 {
+            reasons.push("humidity");
+        }
+        if self.wind_adj != 0 {
+            reasons.push("wind");
+        }
+        reasons
+    }
+
+    /// Names of the runner-related factors affecting the temperature, e.g. "intensity"
+    pub fn runner_reasons(&self) -> Vec<&'static str> {
+        let mut reasons = Vec::new();
+        if self.intensity_adj != 0 {
+            reasons.push("intensity");
+        }
+        if self.duration_adj != 0 {
+            reasons.push("duration");
+        }
+        if self.user_adj != 0 {
+            reasons.push("personal preference");
+        }
+        reasons
+    }
+}
+
+impl DisplayUnits for EffectiveTemperature {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result {
+        write!(f, "{} actual", self.base.in_units(units))?;
+        let weather_reasons = self.weather_reasons();
+        if !weather_reasons.is_empty() {
+            write!(
+                f,
+                ", feels like {} because of {}",
+                self.feels_like().in_units(units),
+                weather_reasons.join(" and ")
+            )?;
+        }
+        let runner_reasons = self.runner_reasons();
+        if !runner_reasons.is_empty() {
+            write!(
+                f,
+                ", dress for {} because of {}",
+                self.total().in_units(units),
+                runner_reasons.join(" and ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for EffectiveTemperature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_units(f, Units::default())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunParameters {
+    pub conditions: Conditions,
+    pub preferences: UserPreferences,
+    effective_temperature: EffectiveTemperature,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub air_quality: Option<AirQuality>,
+}
+
+impl RunParameters {
+    pub fn new(conditions: &Conditions, preferences: &UserPreferences) -> Self {
+        let effective_temperature = EffectiveTemperature::new(conditions, preferences);
+        Self {
+            conditions: conditions.clone(),
+            preferences: preferences.clone(),
+            effective_temperature,
+            air_quality: None,
+        }
+    }
+
+    pub fn with_air_quality(self, air_quality: Option<AirQuality>) -> Self {
+        Self {
+            air_quality,
+            ..self
+        }
+    }
+
+    pub fn effective_temperature(&self) -> Temperature {
+        self.effective_temperature.total()
+    }
+
+    pub const fn effective_temperature_breakdown(&self) -> &EffectiveTemperature {
+        &self.effective_temperature
     }
 }
 
 impl fmt::Display for RunParameters {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}\n{}", self.preferences, self.conditions)
+        let units = self.preferences.units;
+        write!(
+            f,
+            "{}\n{}\n{}",
+            self.preferences,
+            self.conditions.in_units(units),
+            self.effective_temperature.in_units(units)
+        )?;
+        if let Some(air_quality) = &self.air_quality {
+            write!(f, "\nAir quality: {}", air_quality)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::Wind;
+
+    // Effective temperature in overcast weather at the given temperature, so that only the
+    // adjustment under test kicks in
+    fn overcast(
+        fahrenheit: f32,
+        change: impl FnOnce(&mut Conditions, &mut UserPreferences),
+    ) -> EffectiveTemperature {
+        let mut conditions = Conditions {
+            temperature: Temperature::from_fahrenheit(fahrenheit),
+            weather: Weather::Overcast,
+            ..Conditions::default()
+        };
+        let mut preferences = UserPreferences::default();
+        change(&mut conditions, &mut preferences);
+        EffectiveTemperature::new(&conditions, &preferences)
+    }
+
+    #[test]
+    fn test_effective_temperature() {
+        let temperature = overcast(45.0, |conditions, preferences| {
+            conditions.wind = Wind::Light;
+            preferences.intensity = Intensity::Race;
+        });
+        assert_eq!(Temperature::from_fahrenheit(40.0), temperature.feels_like());
+        assert_eq!(Temperature::from_fahrenheit(55.0), temperature.total());
+        assert_eq!(
+            "45°F actual, feels like 40°F because of wind, dress for 55°F because of intensity",
+            temperature.to_string()
+        );
+        assert_eq!(
+            "7°C actual, feels like 4°C because of wind, dress for 13°C because of intensity",
+            temperature.in_units(Units::Metric).to_string()
+        );
+    }
+
+    #[test]
+    fn test_humidity_adjustment() {
+        let temperature = overcast(86.0, |conditions, _| conditions.humidity = Some(60.0));
+        assert_eq!(5, temperature.humidity_adj);
+        assert_eq!(
+            "86°F actual, feels like 91°F because of humidity",
+            temperature.to_string()
+        );
+    }
+
+    #[test]
+    fn test_wind_chill_adjustment() {
+        // Wind chills less as it warms up
+        let windy = |fahrenheit| {
+            overcast(fahrenheit, |conditions, _| {
+                conditions.wind_speed = Some(Speed::from_mph(20.0));
+            })
+            .wind_adj
+        };
+        assert_eq!(-16, windy(20.0));
+        assert_eq!(-6, windy(50.0));
+        assert_eq!(-3, windy(65.0));
+        assert_eq!(0, windy(85.0));
+    }
+
+    #[test]
+    fn test_sun_adjustment() {
+        // A low winter sun doesn't warm as much as a high summer one
+        let sunny = |sun_elevation| {
+            overcast(
+                Conditions::default().temperature.fahrenheit(),
+                |conditions, _| {
+                    conditions.weather = Weather::Clear;
+                    conditions.sun_elevation = Some(sun_elevation);
+                },
+            )
+            .weather_adj
+        };
+        assert_eq!(10, sunny(71.0));
+        assert_eq!(5, sunny(24.0));
+        assert_eq!(0, sunny(-3.0));
+    }
+
+    #[test]
+    fn test_duration_adjustment() {
+        // Short runs end before warming up, and long ones get chilly in the cold
+        let lasting = |fahrenheit, minutes| {
+            overcast(fahrenheit, |_, preferences| {
+                preferences.duration = Some(RunDuration::from_minutes(minutes));
+            })
+        };
+        assert_eq!(-5, lasting(40.0, 20).duration_adj);
+        assert_eq!(0, lasting(40.0, 60).duration_adj);
+        assert_eq!(-3, lasting(40.0, 120).duration_adj);
+        assert_eq!(0, lasting(70.0, 120).duration_adj);
+        assert_eq!(
+            "40°F actual, dress for 37°F because of duration",
+            lasting(40.0, 120).to_string()
+        );
     }
 }
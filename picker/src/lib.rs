@@ -1,6 +1,15 @@
+pub mod air;
 pub mod error;
 pub mod gear;
+pub mod humidity;
 pub mod inputs;
+pub mod planner;
+pub mod providers;
+pub mod rules;
+pub mod safety;
+pub mod sun;
+pub mod trend;
+pub mod units;
 pub mod weather;
 
 pub use error::{Error, Result};
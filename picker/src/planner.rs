@@ -0,0 +1,251 @@
+//! Find the best time to run within an upcoming forecast
+use crate::gear::{GearCatalog, Outfit};
+use crate::inputs::{RunParameters, UserPreferences};
+use crate::units::{DisplayUnits, Temperature, Units};
+use crate::weather::{Conditions, Forecast, TimeOfDay, Weather};
+use crate::{Error, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde_derive::Serialize;
+use std::fmt;
+
+// Range of effective temperatures that are comfortable to run in, once dressed appropriately
+const COMFORTABLE_MIN: Temperature = Temperature::from_fahrenheit(45.0);
+const COMFORTABLE_MAX: Temperature = Temperature::from_fahrenheit(65.0);
+
+/// Penalties for less-than-ideal running conditions. A perfect score is 100.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Score {
+    /// Penalty for the effective temperature being outside of the comfortable range
+    pub temperature: i32,
+    pub precipitation: i32,
+    pub wind: i32,
+    /// Penalty for running in the dark
+    pub daylight: i32,
+}
+
+impl Score {
+    pub fn new(params: &RunParameters) -> Self {
+        let temperature = params.effective_temperature().fahrenheit();
+        let degrees_off = if temperature < COMFORTABLE_MIN.fahrenheit() {
+            COMFORTABLE_MIN.fahrenheit() - temperature
+        } else if temperature > COMFORTABLE_MAX.fahrenheit() {
+            // Heat is harder to dress for than cold
+            2.0 * (temperature - COMFORTABLE_MAX.fahrenheit())
+        } else {
+            0.0
+        };
+        let conditions = &params.conditions;
+        Self {
+            temperature: (degrees_off.round() as i32).min(60),
+            precipitation: match conditions.weather {
+                Weather::Drizzle => 10,
+                Weather::Rain => 20,
+                Weather::Snow => 25,
+                Weather::Sleet => 35,
+                Weather::HeavyRain => 40,
+                // Dangerous, so only worth it if nothing else is possible
+                Weather::Thunderstorm | Weather::FreezingRain => 60,
+                // Not precipitation, but still worth avoiding
+                Weather::Fog | Weather::Haze => 10,
+                Weather::Clear | Weather::PartlyCloudy | Weather::Overcast => 0,
+            },
+            // Wind is already part of how cold it feels, but it's also just harder to run into
+            wind: ((conditions.estimated_wind_speed().mph() * 0.6).round() as i32).min(20),
+            daylight: match conditions.time {
+                TimeOfDay::Night => 20,
+                TimeOfDay::Morning | TimeOfDay::Daytime | TimeOfDay::Evening => 0,
+            },
+        }
+    }
+
+    pub fn total(&self) -> i32 {
+        100 - self.temperature - self.precipitation - self.wind - self.daylight
+    }
+}
+
+/// A forecast time to run at, with how good the conditions are
+#[derive(Debug, Clone, Serialize)]
+pub struct RunWindow {
+    /// Start time, in the location's local time
+    pub start: DateTime<FixedOffset>,
+    pub conditions: Conditions,
+    pub score: Score,
+}
+
+impl DisplayUnits for RunWindow {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}/100, {} and {} with {} wind",
+            self.start.format("%a %-I:%M %p"),
+            self.score.total(),
+            self.conditions.temperature.in_units(units),
+            self.conditions.weather,
+            self.conditions.wind
+        )
+    }
+}
+
+impl fmt::Display for RunWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_units(f, Units::default())
+    }
+}
+
+/// The best time to run, along with what to wear, and the other options considered
+///
+/// Only the forecast's own times are scored, without interpolating between them. Some providers
+/// forecast in 3-hour steps, in which case the best time is only as precise as that.
+#[derive(Debug, Serialize)]
+pub struct Plan {
+    pub best: RunWindow,
+    pub outfit: Outfit,
+    /// Every time considered, in chronological order
+    pub windows: Vec<RunWindow>,
+    /// Minutes between the times considered, if there's more than one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step_minutes: Option<i64>,
+}
+
+impl Plan {
+    /// Score each forecast time between `start` and `end` and pick the best one, preferring
+    /// earlier times in case of a tie
+    pub fn new(
+        forecast: &Forecast,
+        preferences: &UserPreferences,
+        catalog: &GearCatalog,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Self> {
+        let windows: Vec<RunWindow> = forecast
+            .entries
+            .iter()
+            .filter(|entry| entry.time >= start && entry.time <= end)
+            .map(|entry| RunWindow {
+                start: entry.time.with_timezone(&forecast.utc_offset),
+                conditions: entry.conditions.clone(),
+                score: Score::new(&RunParameters::new(&entry.conditions, preferences)),
+            })
+            .collect();
+        let best = windows
+            .iter()
+            .rev()
+            .max_by_key(|window| window.score.total())
+            .cloned()
+            .ok_or_else(|| {
+                Error::InvalidInput("There's no forecast for that time range".to_string())
+            })?;
+        let outfit = Outfit::new(&RunParameters::new(&best.conditions, preferences), catalog)?;
+        let step_minutes = windows
+            .windows(2)
+            .map(|pair| (pair[1].start - pair[0].start).num_minutes())
+            .min();
+        Ok(Self {
+            best,
+            outfit,
+            windows,
+            step_minutes,
+        })
+    }
+
+    /// A heads up that times in between the forecast's aren't considered, if they're far enough
+    /// apart to matter
+    pub fn coarse_step_note(&self) -> Option<String> {
+        self.step_minutes
+            .filter(|&minutes| minutes > 60)
+            .map(|minutes| {
+                format!(
+                    "This forecast only has a time every {} hours, so times in between weren't \
+                considered.",
+                    minutes as f32 / 60.0
+                )
+            })
+    }
+}
+
+impl DisplayUnits for Plan {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result {
+        writeln!(f, "Best time to run: {}", self.best.in_units(units))?;
+        if let Some(note) = self.coarse_step_note() {
+            writeln!(f, "{}", note)?;
+        }
+        writeln!(f, "\nOutfit:\n{}", self.outfit)?;
+        writeln!(f, "All options:")?;
+        for window in &self.windows {
+            writeln!(f, "{}", window.in_units(units))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_units(f, Units::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::ForecastEntry;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_plan() {
+        let start = Utc.timestamp_opt(1_603_000_800, 0).unwrap();
+        let entry =
+            |hours: i64, fahrenheit: f32, weather: Weather, time: TimeOfDay| ForecastEntry {
+                time: start + Duration::hours(hours),
+                conditions: Conditions {
+                    temperature: Temperature::from_fahrenheit(fahrenheit),
+                    weather,
+                    time,
+                    ..Conditions::default()
+                },
+            };
+        let forecast = Forecast {
+            utc_offset: FixedOffset::east_opt(0).unwrap(),
+            entries: vec![
+                entry(0, 50.0, Weather::Clear, TimeOfDay::Night),
+                entry(3, 50.0, Weather::Rain, TimeOfDay::Morning),
+                entry(6, 55.0, Weather::Overcast, TimeOfDay::Daytime),
+                entry(9, 55.0, Weather::Overcast, TimeOfDay::Daytime),
+                entry(12, 90.0, Weather::Clear, TimeOfDay::Daytime),
+                entry(30, 55.0, Weather::Clear, TimeOfDay::Daytime),
+            ],
+            issued_at: None,
+        };
+        let plan = Plan::new(
+            &forecast,
+            &UserPreferences::default(),
+            &GearCatalog::default(),
+            start,
+            start + Duration::hours(24),
+        )
+        .unwrap();
+        assert_eq!(5, plan.windows.len());
+        assert_eq!(start + Duration::hours(6), plan.best.start);
+        assert_eq!(100, plan.best.score.total());
+        assert_eq!(vec!["shorts"], plan.outfit.legs);
+        assert_eq!(20, plan.windows[0].score.daylight);
+        assert_eq!(60, plan.windows[4].score.temperature);
+        assert_eq!(Some(180), plan.step_minutes);
+        assert_eq!(
+            Some(
+                "This forecast only has a time every 3 hours, so times in between weren't \
+                considered."
+                    .to_string()
+            ),
+            plan.coarse_step_note()
+        );
+
+        assert!(Plan::new(
+            &forecast,
+            &UserPreferences::default(),
+            &GearCatalog::default(),
+            start + Duration::hours(40),
+            start + Duration::hours(48),
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use super::{Location, WeatherProvider};
+use crate::air::AirQuality;
+use crate::weather::{Conditions, Forecast};
+use crate::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, warn};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where and for how long to keep weather fetched from a provider
+#[derive(Debug, Clone)]
+pub struct CacheSettings {
+    /// Directory to keep cached weather in
+    pub dir: PathBuf,
+    /// How long to use cached weather before fetching it again
+    pub ttl: Duration,
+    /// How old cached weather can be and still be used when fetching fails, e.g. when offline
+    pub max_stale: Duration,
+}
+
+impl CacheSettings {
+    /// Cache in the given directory for 10 minutes, falling back to weather up to 3 hours old
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            ttl: Duration::minutes(10),
+            max_stale: Duration::hours(3),
+        }
+    }
+}
+
+/// Wraps another provider, reusing recently fetched weather instead of asking the provider again
+///
+/// Weather is cached on disk per provider, location, and kind of request, so that it's shared
+/// between runs of the CLI and between requests to a warm lambda.
+pub struct Cached {
+    provider: Box<dyn WeatherProvider>,
+    settings: CacheSettings,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    fetched_at: DateTime<Utc>,
+    value: T,
+}
+
+// Turn a name into something that's safe to use in a file name
+fn slug(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Coordinates are rounded to about a kilometer so that nearby requests share an entry
+fn location_key(loc: &Location) -> String {
+    match loc {
+        Location::ZipCode { zip, country } => format!("zip_{}_{}", zip, country),
+        Location::City { name, country } => format!("city_{}_{}", name, country),
+        Location::Coordinates { lat, lon } => format!("{:.2}_{:.2}", lat, lon),
+    }
+}
+
+impl Cached {
+    pub fn new(provider: Box<dyn WeatherProvider>, settings: CacheSettings) -> Self {
+        Self { provider, settings }
+    }
+
+    fn path(&self, kind: &str, loc: &Location) -> PathBuf {
+        let name = format!(
+            "{}-{}-{}.json",
+            slug(self.provider.name()),
+            kind,
+            slug(&location_key(loc))
+        );
+        self.settings.dir.join(name)
+    }
+
+    fn read<T: DeserializeOwned>(&self, path: &Path) -> Option<Entry<T>> {
+        let contents = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&contents) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                debug!("Ignoring unreadable cache entry {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn write<T: Serialize>(&self, path: &Path, entry: &Entry<T>) {
+        // Failing to cache shouldn't fail the request
+        let result = fs::create_dir_all(&self.settings.dir)
+            .map_err(|e| e.to_string())
+            .and_then(|_| serde_json::to_string(entry).map_err(|e| e.to_string()))
+            .and_then(|json| fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!("Failed to cache weather in {}: {}", path.display(), e);
+        }
+    }
+
+    // Use the cached value if it's fresh enough, otherwise fetch a new one, falling back to a
+    // stale cached value if fetching fails
+    fn get<T: Serialize + DeserializeOwned>(
+        &self,
+        kind: &str,
+        loc: &Location,
+        fetch: impl FnOnce() -> Result<T>,
+    ) -> Result<T> {
+        let path = self.path(kind, loc);
+        let now = Utc::now();
+        // Entries from the future, e.g. after the clock was changed, count as expired
+        let cached = match self
+            .read::<T>(&path)
+            .filter(|entry| entry.fetched_at <= now)
+        {
+            Some(entry) if now - entry.fetched_at < self.settings.ttl => {
+                debug!("Using cached weather from {}", path.display());
+                return Ok(entry.value);
+            }
+            cached => cached,
+        };
+        match fetch() {
+            Ok(value) => {
+                let entry = Entry {
+                    fetched_at: now,
+                    value,
+                };
+                self.write(&path, &entry);
+                Ok(entry.value)
+            }
+            Err(e @ Error::FetchWeather { .. }) => match cached {
+                Some(entry) if now - entry.fetched_at < self.settings.max_stale => {
+                    warn!(
+                        "{}. Using weather cached {} minutes ago instead.",
+                        e,
+                        (now - entry.fetched_at).num_minutes()
+                    );
+                    Ok(entry.value)
+                }
+                _ => Err(e),
+            },
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl WeatherProvider for Cached {
+    fn name(&self) -> &'static str {
+        self.provider.name()
+    }
+
+    fn current(&self, loc: &Location) -> Result<Conditions> {
+        self.get("current", loc, || self.provider.current(loc))
+    }
+
+    fn forecast(&self, loc: &Location) -> Result<Forecast> {
+        self.get("forecast", loc, || self.provider.forecast(loc))
+    }
+
+    fn air_quality(&self, loc: &Location) -> Result<Option<AirQuality>> {
+        self.get("air_quality", loc, || self.provider.air_quality(loc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Temperature;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // Reports a different temperature each time it's asked, or fails if told to
+    struct Counter {
+        calls: Rc<Cell<u32>>,
+        offline: Rc<Cell<bool>>,
+    }
+
+    impl WeatherProvider for Counter {
+        fn name(&self) -> &'static str {
+            "Counter"
+        }
+
+        fn current(&self, _loc: &Location) -> Result<Conditions> {
+            if self.offline.get() {
+                return Err(Error::fetch_weather(self.name(), "offline"));
+            }
+            self.calls.set(self.calls.get() + 1);
+            Ok(Conditions {
+                temperature: Temperature::from_fahrenheit(self.calls.get() as f32),
+                ..Conditions::default()
+            })
+        }
+
+        fn forecast(&self, loc: &Location) -> Result<Forecast> {
+            Err(Error::InvalidInput(format!("No forecast for {}", loc)))
+        }
+    }
+
+    #[test]
+    fn test_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let calls = Rc::new(Cell::new(0));
+        let offline = Rc::new(Cell::new(false));
+        let counter = || {
+            Box::new(Counter {
+                calls: calls.clone(),
+                offline: offline.clone(),
+            })
+        };
+        let loc = Location::Coordinates {
+            lat: 42.3876,
+            lon: -71.0995,
+        };
+        let nearby = Location::Coordinates {
+            lat: 42.3901,
+            lon: -71.1002,
+        };
+        let temperature = |cached: &Cached, loc| cached.current(loc).unwrap().temperature;
+
+        // Fresh entries are shared between instances and nearby locations
+        let settings = CacheSettings::new(dir.path().to_path_buf());
+        let cached = Cached::new(counter(), settings.clone());
+        assert_eq!(
+            Temperature::from_fahrenheit(1.0),
+            temperature(&cached, &loc)
+        );
+        let cached = Cached::new(counter(), settings.clone());
+        assert_eq!(
+            Temperature::from_fahrenheit(1.0),
+            temperature(&cached, &nearby)
+        );
+        assert_eq!(1, calls.get());
+
+        // Expired entries are fetched again, but still used if fetching fails
+        let expired = CacheSettings {
+            ttl: Duration::zero(),
+            ..settings.clone()
+        };
+        let cached = Cached::new(counter(), expired.clone());
+        assert_eq!(
+            Temperature::from_fahrenheit(2.0),
+            temperature(&cached, &loc)
+        );
+        offline.set(true);
+        assert_eq!(
+            Temperature::from_fahrenheit(2.0),
+            temperature(&cached, &loc)
+        );
+
+        // Unless they're too old
+        let cached = Cached::new(
+            counter(),
+            CacheSettings {
+                max_stale: Duration::zero(),
+                ..expired
+            },
+        );
+        assert!(matches!(
+            cached.current(&loc),
+            Err(Error::FetchWeather { .. })
+        ));
+
+        // Other errors aren't covered up
+        assert!(matches!(cached.forecast(&loc), Err(Error::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,79 @@
+use super::{Location, WeatherProvider};
+use crate::weather::{Conditions, Forecast};
+use crate::{Error, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Canned weather that's the same everywhere, for testing and for trying things out offline
+///
+/// Can be loaded from JSON, e.g.
+///
+/// ```json
+/// {
+///     "current": { "temperature": 45, "weather": "rain" },
+///     "forecast": {
+///         "utc_offset": -14400,
+///         "entries": [
+///             { "time": "2020-10-18T10:00:00Z", "conditions": { "temperature": 50 } }
+///         ]
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub current: Conditions,
+    pub forecast: Forecast,
+}
+
+impl Fixture {
+    pub fn from_json(s: &str) -> Result<Self> {
+        let fixture: Self = serde_json::from_str(s).map_err(Error::ParseFixture)?;
+        fixture.current.validate()?;
+        Ok(fixture)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path).map_err(Error::ReadFixture)?)
+    }
+}
+
+impl WeatherProvider for Fixture {
+    fn name(&self) -> &'static str {
+        "fixture"
+    }
+
+    fn current(&self, _loc: &Location) -> Result<Conditions> {
+        Ok(self.current.clone())
+    }
+
+    fn forecast(&self, _loc: &Location) -> Result<Forecast> {
+        Ok(self.forecast.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::Weather;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_fixture() {
+        let fixture =
+            Fixture::from_json(include_str!("../../tests/fixtures/fixture.json")).unwrap();
+        let loc = Location::ZipCode {
+            zip: "02144".to_string(),
+            country: "US".to_string(),
+        };
+        assert_eq!(Weather::Rain, fixture.current(&loc).unwrap().weather);
+        let time = Utc.with_ymd_and_hms(2020, 10, 18, 12, 0, 0).unwrap();
+        assert_eq!(Weather::Overcast, fixture.at(&loc, time).unwrap().weather);
+        assert!(fixture.at(&loc, Utc::now()).is_err());
+
+        assert!(matches!(
+            Fixture::from_json(r#"{ "current": { "temperature": 20, "weather": "rain" } }"#),
+            Err(Error::ParseFixture(_))
+        ));
+    }
+}
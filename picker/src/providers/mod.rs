@@ -0,0 +1,149 @@
+//! Sources of weather data
+//!
+//! Everything that needs the weather goes through the [`WeatherProvider`] trait so that the source
+//! can be swapped out, e.g. for canned data in tests.
+use crate::air::AirQuality;
+use crate::weather::{Conditions, Forecast};
+use crate::{inputs::parse_choice, Error, Result};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+mod cache;
+mod fixture;
+mod nws;
+mod open_meteo;
+mod owm;
+#[cfg(test)]
+mod test_server;
+
+pub use cache::{CacheSettings, Cached};
+pub use fixture::Fixture;
+pub use nws::Nws;
+pub use open_meteo::OpenMeteo;
+pub use owm::OpenWeatherMap;
+
+/// Where to get the weather for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Location {
+    ZipCode { zip: String, country: String },
+    City { name: String, country: String },
+    Coordinates { lat: f32, lon: f32 },
+}
+
+impl Location {
+    /// Latitude and longitude, if the location was given as coordinates
+    pub fn coordinates(&self) -> Option<(f32, f32)> {
+        match self {
+            Self::Coordinates { lat, lon } => Some((*lat, *lon)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ZipCode { zip, country } => write!(f, "{}, {}", zip, country),
+            Self::City { name, country } => write!(f, "{}, {}", name, country),
+            Self::Coordinates { lat, lon } => write!(f, "{}, {}", lat, lon),
+        }
+    }
+}
+
+pub trait WeatherProvider {
+    /// Human-readable name, e.g. "OpenWeatherMap"
+    fn name(&self) -> &'static str;
+
+    /// Current conditions at the given location
+    fn current(&self, loc: &Location) -> Result<Conditions>;
+
+    /// Upcoming conditions at the given location
+    fn forecast(&self, loc: &Location) -> Result<Forecast>;
+
+    /// Forecast conditions at an upcoming time
+    fn at(&self, loc: &Location, time: DateTime<Utc>) -> Result<Conditions> {
+        self.forecast(loc)?.at(time)
+    }
+
+    /// Current air quality at the given location, or `None` if the provider doesn't have it
+    fn air_quality(&self, _loc: &Location) -> Result<Option<AirQuality>> {
+        Ok(None)
+    }
+}
+
+/// The supported weather providers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    #[serde(rename = "openweathermap")]
+    OpenWeatherMap,
+    /// US National Weather Service
+    Nws,
+    /// Open-Meteo, which doesn't need an API key
+    OpenMeteo,
+    /// Canned conditions read from a file
+    Fixture,
+}
+
+impl Default for ProviderKind {
+    fn default() -> Self {
+        Self::OpenWeatherMap
+    }
+}
+
+impl FromStr for ProviderKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_choice(
+            "weather provider",
+            s,
+            &[
+                ("openweathermap", Self::OpenWeatherMap),
+                ("nws", Self::Nws),
+                ("open-meteo", Self::OpenMeteo),
+                ("fixture", Self::Fixture),
+            ],
+        )
+    }
+}
+
+/// Settings needed by the various providers. Each provider only looks at the settings it needs.
+#[derive(Debug, Default, Clone)]
+pub struct ProviderSettings {
+    pub owm_api_key: Option<String>,
+    /// File to read canned conditions from
+    pub fixture: Option<PathBuf>,
+    /// Where to cache weather fetched from the network, if anywhere
+    pub cache: Option<CacheSettings>,
+}
+
+impl ProviderKind {
+    pub fn build(self, settings: &ProviderSettings) -> Result<Box<dyn WeatherProvider>> {
+        let provider: Box<dyn WeatherProvider> = match self {
+            Self::OpenWeatherMap => {
+                let api_key = settings.owm_api_key.clone().ok_or_else(|| {
+                    Error::ConfigureProvider("No OpenWeatherMap API key provided".to_string())
+                })?;
+                Box::new(OpenWeatherMap::new(api_key))
+            }
+            Self::Nws => Box::new(Nws::new()),
+            Self::OpenMeteo => Box::new(OpenMeteo::new()),
+            Self::Fixture => {
+                let path = settings.fixture.as_ref().ok_or_else(|| {
+                    Error::ConfigureProvider("No fixture file provided".to_string())
+                })?;
+                // Already local, so there's nothing to gain from caching
+                return Ok(Box::new(Fixture::load(path)?));
+            }
+        };
+        Ok(match &settings.cache {
+            Some(cache) => Box::new(Cached::new(provider, cache.clone())),
+            None => provider,
+        })
+    }
+}
@@ -0,0 +1,437 @@
+use super::{Location, WeatherProvider};
+use crate::sun::SunPosition;
+use crate::units::{Speed, Temperature};
+use crate::weather::{Conditions, Forecast, ForecastEntry, TimeOfDay, Weather, Wind};
+use crate::{Error, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use log::debug;
+use reqwest::blocking::Client;
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+
+const NAME: &str = "National Weather Service";
+const BASE_URL: &str = "https://api.weather.gov";
+// The API asks that clients identify themselves
+const USER_AGENT: &str = "wtw-running (https://github.com/kesyog/wtw-running)";
+
+/// Weather from the US [National Weather Service](https://www.weather.gov/documentation/services-web-api)
+///
+/// Doesn't need an API key, but only covers the US and only accepts coordinates.
+pub struct Nws {
+    client: Client,
+    base_url: String,
+}
+
+impl Nws {
+    pub fn new() -> Self {
+        Self::with_base_url(BASE_URL)
+    }
+
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.to_string(),
+        }
+    }
+
+    fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        debug!("Fetching {}", url);
+        self.client
+            .get(url)
+            .header(reqwest::header::USER_AGENT, USER_AGENT)
+            .header(reqwest::header::ACCEPT, "application/geo+json")
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .map_err(|e| Error::fetch_weather(NAME, e))
+    }
+
+    // Look up the forecast and observation URLs for a location
+    fn point(&self, loc: &Location) -> Result<PointProperties> {
+        let (lat, lon) = loc.coordinates().ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "The {} needs a location given as coordinates",
+                NAME
+            ))
+        })?;
+        let url = format!("{}/points/{:.4},{:.4}", self.base_url, lat, lon);
+        Ok(self.get::<Point>(&url)?.properties)
+    }
+}
+
+impl Default for Nws {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Deserialize)]
+struct Point {
+    properties: PointProperties,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PointProperties {
+    forecast_hourly: String,
+    observation_stations: String,
+}
+
+#[derive(Deserialize)]
+struct Stations {
+    features: Vec<Station>,
+}
+
+#[derive(Deserialize)]
+struct Station {
+    /// URL of the station
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct Observation {
+    properties: ObservationProperties,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObservationProperties {
+    timestamp: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    text_description: String,
+    icon: Option<String>,
+    temperature: Measurement,
+    #[serde(default)]
+    dewpoint: Measurement,
+    #[serde(default)]
+    relative_humidity: Measurement,
+    wind_speed: Measurement,
+    #[serde(default)]
+    wind_gust: Measurement,
+    #[serde(default)]
+    cloud_layers: Vec<CloudLayer>,
+}
+
+// A value in the units given by the API, which is °C for temperatures and km/h for speeds
+#[derive(Default, Deserialize)]
+struct Measurement {
+    value: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct CloudLayer {
+    /// METAR sky cover code, e.g. "BKN"
+    amount: String,
+}
+
+#[derive(Deserialize)]
+struct HourlyForecast {
+    properties: HourlyForecastProperties,
+}
+
+#[derive(Deserialize)]
+struct HourlyForecastProperties {
+    periods: Vec<Period>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Period {
+    start_time: DateTime<FixedOffset>,
+    temperature: f32,
+    temperature_unit: String,
+    /// e.g. "10 mph" or "5 to 10 mph"
+    wind_speed: String,
+    #[serde(default)]
+    dewpoint: Measurement,
+    #[serde(default)]
+    relative_humidity: Measurement,
+    short_forecast: String,
+}
+
+// Resolve weather from a short description like "Chance Light Rain" or "Mostly Sunny", returning
+// `None` if the description doesn't say anything useful
+fn resolve_description(description: &str) -> Option<Weather> {
+    let description = description.to_lowercase();
+    let has = |word: &str| description.contains(word);
+    if has("thunder") || has("t-storm") {
+        Some(Weather::Thunderstorm)
+    } else if has("freezing rain") || has("freezing drizzle") {
+        Some(Weather::FreezingRain)
+    } else if has("sleet")
+        || has("ice pellets")
+        || has("wintry mix")
+        || has("rain and snow")
+        || has("rain/snow")
+        || has("snow and rain")
+    {
+        Some(Weather::Sleet)
+    } else if has("snow") {
+        Some(Weather::Snow)
+    } else if has("heavy rain") {
+        Some(Weather::HeavyRain)
+    } else if has("drizzle") {
+        Some(Weather::Drizzle)
+    } else if has("rain") || has("shower") {
+        Some(Weather::Rain)
+    } else if has("fog") || has("mist") {
+        Some(Weather::Fog)
+    } else if has("haze") || has("smoke") || has("dust") {
+        Some(Weather::Haze)
+    } else if has("partly") || has("mostly sunny") || has("mostly clear") {
+        Some(Weather::PartlyCloudy)
+    } else if has("cloudy") || has("overcast") {
+        Some(Weather::Overcast)
+    } else if has("sunny") || has("clear") || has("fair") {
+        Some(Weather::Clear)
+    } else {
+        None
+    }
+}
+
+// Resolve weather from METAR sky cover codes, using the most covered layer
+fn resolve_sky_cover(layers: &[CloudLayer]) -> Weather {
+    let cover = |layer: &CloudLayer| match layer.amount.as_str() {
+        "OVC" | "VV" => 2,
+        "BKN" | "SCT" => 1,
+        _ => 0,
+    };
+    match layers.iter().map(cover).max().unwrap_or_default() {
+        2 => Weather::Overcast,
+        1 => Weather::PartlyCloudy,
+        _ => Weather::Clear,
+    }
+}
+
+// Parse the fastest speed out of a description like "5 to 10 mph"
+fn parse_wind_speed(s: &str) -> Speed {
+    let mph = s
+        .split_whitespace()
+        .filter_map(|word| word.parse::<f32>().ok())
+        .fold(0.0, f32::max);
+    Speed::from_mph(mph)
+}
+
+impl From<&ObservationProperties> for Conditions {
+    fn from(observation: &ObservationProperties) -> Self {
+        let weather = resolve_description(&observation.text_description)
+            .filter(|weather| !matches!(weather, Weather::Clear | Weather::PartlyCloudy))
+            .unwrap_or_else(|| resolve_sky_cover(&observation.cloud_layers));
+        // Icon URLs look like https://api.weather.gov/icons/land/night/ovc. This is only a rough
+        // guess until the sun's position is known.
+        let time = match &observation.icon {
+            Some(icon) if icon.contains("/night/") => TimeOfDay::Night,
+            _ => TimeOfDay::Daytime,
+        };
+        let wind_speed = observation.wind_speed.value.map(Speed::from_kph);
+        Self {
+            temperature: Temperature::from_celsius(
+                observation.temperature.value.unwrap_or_default(),
+            ),
+            weather,
+            wind: wind_speed.map(Wind::from_speed).unwrap_or_default(),
+            time,
+            wind_speed,
+            wind_gust: observation.wind_gust.value.map(Speed::from_kph),
+            humidity: observation.relative_humidity.value,
+            dew_point: observation.dewpoint.value.map(Temperature::from_celsius),
+            observed_at: observation
+                .timestamp
+                .map(|timestamp| timestamp.with_timezone(&Utc)),
+            ..Conditions::default()
+        }
+    }
+}
+
+impl WeatherProvider for Nws {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn current(&self, loc: &Location) -> Result<Conditions> {
+        let point = self.point(loc)?;
+        let (lat, lon) = loc.coordinates().unwrap_or_default();
+        let stations: Stations = self.get(&point.observation_stations)?;
+        // Stations are sorted by distance
+        let station = stations
+            .features
+            .first()
+            .ok_or_else(|| Error::fetch_weather(NAME, "No observation stations nearby"))?;
+        let observation: Observation = self.get(&format!("{}/observations/latest", station.id))?;
+        if observation.properties.temperature.value.is_none() {
+            return Err(Error::fetch_weather(
+                NAME,
+                "Latest observation is missing the temperature",
+            ));
+        }
+        let mut conditions = Conditions::from(&observation.properties);
+        if let Some(observed_at) = conditions.observed_at {
+            conditions.set_sun_position(SunPosition::new(lat, lon, observed_at));
+        }
+        conditions.validate()?;
+        Ok(conditions)
+    }
+
+    fn forecast(&self, loc: &Location) -> Result<Forecast> {
+        let point = self.point(loc)?;
+        let (lat, lon) = loc.coordinates().unwrap_or_default();
+        let forecast: HourlyForecast = self.get(&point.forecast_hourly)?;
+        let periods = &forecast.properties.periods;
+        let utc_offset = periods
+            .first()
+            .map(|period| *period.start_time.offset())
+            .ok_or_else(|| Error::fetch_weather(NAME, "Forecast is empty"))?;
+        let entries = periods
+            .iter()
+            .filter_map(|period| {
+                let time = period.start_time.with_timezone(&Utc);
+                let sun = SunPosition::new(lat, lon, time);
+                let temperature = if period.temperature_unit == "C" {
+                    Temperature::from_celsius(period.temperature)
+                } else {
+                    Temperature::from_fahrenheit(period.temperature)
+                };
+                let wind_speed = parse_wind_speed(&period.wind_speed);
+                let conditions = Conditions {
+                    temperature,
+                    weather: resolve_description(&period.short_forecast).unwrap_or_default(),
+                    wind: Wind::from_speed(wind_speed),
+                    time: sun.time_of_day(),
+                    sun_elevation: Some(sun.elevation),
+                    wind_speed: Some(wind_speed),
+                    humidity: period.relative_humidity.value,
+                    dew_point: period.dewpoint.value.map(Temperature::from_celsius),
+                    ..Conditions::default()
+                };
+                // Skip entries that don't make sense rather than failing the whole forecast
+                conditions.validate().ok()?;
+                Some(ForecastEntry { time, conditions })
+            })
+            .collect();
+        Ok(Forecast {
+            utc_offset,
+            entries,
+            issued_at: Some(Utc::now()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::test_server::TestServer;
+    use chrono::TimeZone;
+
+    fn start_server() -> TestServer {
+        TestServer::start(
+            BASE_URL,
+            &[
+                (
+                    "/points/42.3876,-71.0995",
+                    include_str!("../../tests/fixtures/nws_points.json"),
+                ),
+                (
+                    "/gridpoints/BOX/69,92/stations",
+                    include_str!("../../tests/fixtures/nws_stations.json"),
+                ),
+                (
+                    "/stations/KBOS/observations/latest",
+                    include_str!("../../tests/fixtures/nws_observation.json"),
+                ),
+                (
+                    "/gridpoints/BOX/69,92/forecast/hourly",
+                    include_str!("../../tests/fixtures/nws_forecast_hourly.json"),
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_nws() {
+        let server = start_server();
+        let nws = Nws::with_base_url(&server.base_url);
+        let loc = Location::Coordinates {
+            lat: 42.3876,
+            lon: -71.0995,
+        };
+
+        let current = nws.current(&loc).unwrap();
+        assert_eq!(Temperature::from_celsius(8.3), current.temperature);
+        assert_eq!(Weather::Rain, current.weather);
+        assert_eq!(Wind::Light, current.wind);
+        assert_eq!(Some(Speed::from_kph(20.5)), current.wind_speed);
+        assert_eq!(None, current.wind_gust);
+        // Just before sunrise
+        assert_eq!(TimeOfDay::Morning, current.time);
+        assert_eq!(Some(86.0), current.humidity);
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2020, 10, 18, 10, 54, 0).unwrap()),
+            current.observed_at
+        );
+
+        let forecast = nws.forecast(&loc).unwrap();
+        assert_eq!(-4 * 3600, forecast.utc_offset.local_minus_utc());
+        let times: Vec<TimeOfDay> = forecast
+            .entries
+            .iter()
+            .map(|entry| entry.conditions.time)
+            .collect();
+        assert_eq!(
+            vec![
+                TimeOfDay::Night,
+                TimeOfDay::Morning,
+                TimeOfDay::Morning,
+                TimeOfDay::Evening,
+                TimeOfDay::Evening
+            ],
+            times
+        );
+        let conditions = nws
+            .at(&loc, Utc.with_ymd_and_hms(2020, 10, 18, 11, 0, 0).unwrap())
+            .unwrap();
+        assert_eq!(Weather::Rain, conditions.weather);
+        assert_eq!(Wind::Light, conditions.wind);
+
+        let zip = Location::ZipCode {
+            zip: "02144".to_string(),
+            country: "US".to_string(),
+        };
+        assert!(matches!(nws.current(&zip), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_resolve_description() {
+        assert_eq!(
+            Some(Weather::Rain),
+            resolve_description("Chance Light Rain")
+        );
+        assert_eq!(
+            Some(Weather::PartlyCloudy),
+            resolve_description("Mostly Sunny")
+        );
+        assert_eq!(
+            Some(Weather::Overcast),
+            resolve_description("Mostly Cloudy")
+        );
+        assert_eq!(Some(Weather::Sleet), resolve_description("Rain And Snow"));
+        assert_eq!(
+            Some(Weather::Sleet),
+            resolve_description("Chance Rain/Snow")
+        );
+        assert_eq!(Some(Weather::Snow), resolve_description("Light Snow"));
+        assert_eq!(
+            Some(Weather::Thunderstorm),
+            resolve_description("Chance Showers And Thunderstorms")
+        );
+        assert_eq!(
+            Some(Weather::FreezingRain),
+            resolve_description("Freezing Drizzle")
+        );
+        assert_eq!(Some(Weather::Fog), resolve_description("Patchy Fog"));
+        assert_eq!(
+            Some(Weather::Fog),
+            resolve_description("Patchy Freezing Fog")
+        );
+        assert_eq!(None, resolve_description("Breezy"));
+    }
+}
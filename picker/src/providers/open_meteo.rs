@@ -0,0 +1,315 @@
+use super::{Location, WeatherProvider};
+use crate::sun::SunPosition;
+use crate::units::{Speed, Temperature};
+use crate::weather::{Conditions, Forecast, ForecastEntry, Weather, Wind};
+use crate::{Error, Result};
+use chrono::{FixedOffset, TimeZone, Utc};
+use log::debug;
+use reqwest::blocking::Client;
+use serde_derive::Deserialize;
+
+const NAME: &str = "Open-Meteo";
+const BASE_URL: &str = "https://api.open-meteo.com";
+// Hourly variables to request, which are also requested for the current conditions
+const VARIABLES: &str = "temperature_2m,apparent_temperature,relative_humidity_2m,dew_point_2m,\
+    weather_code,wind_speed_10m,wind_gusts_10m,precipitation,uv_index";
+// Rain heavier than this in mm/h counts as heavy rain, whatever the weather code says
+const HEAVY_RAIN_MM: f32 = 4.0;
+
+/// Weather from [Open-Meteo](https://open-meteo.com/en/docs)
+///
+/// Doesn't need an API key and covers the whole world, but only accepts coordinates.
+pub struct OpenMeteo {
+    client: Client,
+    base_url: String,
+}
+
+impl OpenMeteo {
+    pub fn new() -> Self {
+        Self::with_base_url(BASE_URL)
+    }
+
+    pub fn with_base_url(base_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.to_string(),
+        }
+    }
+
+    // Current and hourly conditions come back from the same request
+    fn fetch(&self, loc: &Location) -> Result<OpenMeteoResponse> {
+        let (lat, lon) = loc.coordinates().ok_or_else(|| {
+            Error::InvalidInput(format!("{} needs a location given as coordinates", NAME))
+        })?;
+        let url = format!("{}/v1/forecast", self.base_url);
+        debug!("Fetching {} for {}, {}", url, lat, lon);
+        self.client
+            .get(&url)
+            .query(&[
+                ("latitude", lat.to_string()),
+                ("longitude", lon.to_string()),
+                ("current", VARIABLES.to_string()),
+                ("hourly", VARIABLES.to_string()),
+                ("temperature_unit", "celsius".to_string()),
+                ("wind_speed_unit", "kmh".to_string()),
+                ("timezone", "auto".to_string()),
+                ("timeformat", "unixtime".to_string()),
+                ("forecast_days", "3".to_string()),
+            ])
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .map_err(|e| Error::fetch_weather(NAME, e))
+    }
+}
+
+impl Default for OpenMeteo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenMeteoResponse {
+    latitude: f32,
+    longitude: f32,
+    utc_offset_seconds: i32,
+    current: Current,
+    hourly: Hourly,
+}
+
+// Temperatures are in °C, speeds in km/h, and precipitation in mm over the preceding hour
+#[derive(Deserialize)]
+struct Current {
+    /// Unix timestamp
+    time: i64,
+    temperature_2m: f32,
+    apparent_temperature: Option<f32>,
+    relative_humidity_2m: Option<f32>,
+    dew_point_2m: Option<f32>,
+    weather_code: u8,
+    wind_speed_10m: f32,
+    wind_gusts_10m: Option<f32>,
+    precipitation: f32,
+    uv_index: Option<f32>,
+}
+
+// Each variable is an array with a value per hour. Values can be missing at the end of the range.
+#[derive(Deserialize)]
+struct Hourly {
+    /// Unix timestamps
+    time: Vec<i64>,
+    temperature_2m: Vec<Option<f32>>,
+    apparent_temperature: Vec<Option<f32>>,
+    #[serde(default)]
+    relative_humidity_2m: Vec<Option<f32>>,
+    #[serde(default)]
+    dew_point_2m: Vec<Option<f32>>,
+    weather_code: Vec<Option<u8>>,
+    wind_speed_10m: Vec<Option<f32>>,
+    #[serde(default)]
+    wind_gusts_10m: Vec<Option<f32>>,
+    precipitation: Vec<Option<f32>>,
+    uv_index: Vec<Option<f32>>,
+}
+
+// Resolve weather from a WMO weather interpretation code and the amount of precipitation
+fn resolve_weather_code(code: u8, precipitation: f32) -> Weather {
+    let weather = match code {
+        0 | 1 => Weather::Clear,
+        2 => Weather::PartlyCloudy,
+        3 => Weather::Overcast,
+        // Fog and rime fog
+        45 | 48 => Weather::Fog,
+        51..=55 => Weather::Drizzle,
+        // Freezing drizzle and freezing rain
+        56 | 57 | 66 | 67 => Weather::FreezingRain,
+        // Rain and rain showers
+        61 | 63 | 80 | 81 => Weather::Rain,
+        65 | 82 => Weather::HeavyRain,
+        // Snow, snow grains, and snow showers
+        71..=77 | 85 | 86 => Weather::Snow,
+        95..=99 => Weather::Thunderstorm,
+        _ => Weather::default(),
+    };
+    match weather {
+        Weather::Rain if precipitation > HEAVY_RAIN_MM => Weather::HeavyRain,
+        _ => weather,
+    }
+}
+
+impl OpenMeteoResponse {
+    fn sun_position(&self, timestamp: i64) -> Option<SunPosition> {
+        let time = Utc.timestamp_opt(timestamp, 0).single()?;
+        Some(SunPosition::new(self.latitude, self.longitude, time))
+    }
+
+    fn current_conditions(&self) -> Conditions {
+        let current = &self.current;
+        let wind_speed = Speed::from_kph(current.wind_speed_10m);
+        let sun = self.sun_position(current.time);
+        Conditions {
+            temperature: Temperature::from_celsius(current.temperature_2m),
+            weather: resolve_weather_code(current.weather_code, current.precipitation),
+            wind: Wind::from_speed(wind_speed),
+            time: sun.map(|sun| sun.time_of_day()).unwrap_or_default(),
+            sun_elevation: sun.map(|sun| sun.elevation),
+            apparent_temperature: current.apparent_temperature.map(Temperature::from_celsius),
+            uv_index: current.uv_index,
+            wind_speed: Some(wind_speed),
+            wind_gust: current.wind_gusts_10m.map(Speed::from_kph),
+            humidity: current.relative_humidity_2m,
+            dew_point: current.dew_point_2m.map(Temperature::from_celsius),
+            observed_at: Utc.timestamp_opt(current.time, 0).single(),
+        }
+    }
+}
+
+impl WeatherProvider for OpenMeteo {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn current(&self, loc: &Location) -> Result<Conditions> {
+        let conditions = self.fetch(loc)?.current_conditions();
+        conditions.validate()?;
+        Ok(conditions)
+    }
+
+    fn forecast(&self, loc: &Location) -> Result<Forecast> {
+        let response = self.fetch(loc)?;
+        let utc_offset = FixedOffset::east_opt(response.utc_offset_seconds)
+            .ok_or_else(|| Error::fetch_weather(NAME, "Invalid UTC offset"))?;
+        let hourly = &response.hourly;
+        let value = |values: &[Option<f32>], i: usize| values.get(i).copied().flatten();
+        let entries = hourly
+            .time
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &timestamp)| {
+                // Skip hours that are missing the basics rather than failing the whole forecast
+                let temperature = value(&hourly.temperature_2m, i)?;
+                let sun = response.sun_position(timestamp)?;
+                let code = hourly.weather_code.get(i).copied().flatten()?;
+                let precipitation = value(&hourly.precipitation, i).unwrap_or_default();
+                let wind_speed = value(&hourly.wind_speed_10m, i).map(Speed::from_kph);
+                let conditions = Conditions {
+                    temperature: Temperature::from_celsius(temperature),
+                    weather: resolve_weather_code(code, precipitation),
+                    wind: wind_speed.map(Wind::from_speed).unwrap_or_default(),
+                    time: sun.time_of_day(),
+                    sun_elevation: Some(sun.elevation),
+                    apparent_temperature: value(&hourly.apparent_temperature, i)
+                        .map(Temperature::from_celsius),
+                    uv_index: value(&hourly.uv_index, i),
+                    wind_speed,
+                    wind_gust: value(&hourly.wind_gusts_10m, i).map(Speed::from_kph),
+                    humidity: value(&hourly.relative_humidity_2m, i),
+                    dew_point: value(&hourly.dew_point_2m, i).map(Temperature::from_celsius),
+                    observed_at: None,
+                };
+                conditions.validate().ok()?;
+                Some(ForecastEntry {
+                    time: Utc.timestamp_opt(timestamp, 0).single()?,
+                    conditions,
+                })
+            })
+            .collect();
+        Ok(Forecast {
+            utc_offset,
+            entries,
+            issued_at: Some(Utc::now()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::test_server::TestServer;
+    use crate::weather::TimeOfDay;
+
+    #[test]
+    fn test_open_meteo() {
+        let server = TestServer::start(
+            BASE_URL,
+            &[(
+                "/v1/forecast",
+                include_str!("../../tests/fixtures/open_meteo_forecast.json"),
+            )],
+        );
+        let open_meteo = OpenMeteo::with_base_url(&server.base_url);
+        let loc = Location::Coordinates {
+            lat: 42.3876,
+            lon: -71.0995,
+        };
+
+        let current = open_meteo.current(&loc).unwrap();
+        assert_eq!(Temperature::from_celsius(9.4), current.temperature);
+        assert_eq!(Weather::Rain, current.weather);
+        assert_eq!(Wind::Light, current.wind);
+        assert_eq!(Some(Speed::from_kph(31.3)), current.wind_gust);
+        // Just before sunrise
+        assert_eq!(TimeOfDay::Morning, current.time);
+        assert_eq!(-1.0, current.sun_elevation.unwrap().round());
+        assert_eq!(
+            Some(Temperature::from_celsius(6.1)),
+            current.apparent_temperature
+        );
+        assert_eq!(Some(0.85), current.uv_index);
+        assert_eq!(Some(87.0), current.humidity);
+        assert_eq!(
+            Some(Utc.with_ymd_and_hms(2020, 10, 18, 11, 0, 0).unwrap()),
+            current.observed_at
+        );
+
+        let forecast = open_meteo.forecast(&loc).unwrap();
+        assert_eq!(-4 * 3600, forecast.utc_offset.local_minus_utc());
+        // The last hour is missing its temperature
+        assert_eq!(7, forecast.entries.len());
+        let times: Vec<TimeOfDay> = forecast
+            .entries
+            .iter()
+            .map(|entry| entry.conditions.time)
+            .collect();
+        assert_eq!(
+            vec![
+                TimeOfDay::Night,
+                TimeOfDay::Night,
+                TimeOfDay::Morning,
+                TimeOfDay::Morning,
+                TimeOfDay::Daytime,
+                TimeOfDay::Daytime,
+                TimeOfDay::Daytime
+            ],
+            times
+        );
+        let conditions = open_meteo
+            .at(&loc, Utc.with_ymd_and_hms(2020, 10, 18, 13, 0, 0).unwrap())
+            .unwrap();
+        // Moderate rain by code, but heavy by amount
+        assert_eq!(Weather::HeavyRain, conditions.weather);
+        assert_eq!(Wind::Heavy, conditions.wind);
+        assert_eq!(Some(2.4), conditions.uv_index);
+
+        let city = Location::City {
+            name: "Somerville".to_string(),
+            country: "US".to_string(),
+        };
+        assert!(matches!(
+            open_meteo.current(&city),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_weather_code() {
+        assert_eq!(Weather::Clear, resolve_weather_code(1, 0.0));
+        assert_eq!(Weather::Fog, resolve_weather_code(45, 0.0));
+        assert_eq!(Weather::Drizzle, resolve_weather_code(53, 0.2));
+        assert_eq!(Weather::FreezingRain, resolve_weather_code(56, 0.2));
+        assert_eq!(Weather::HeavyRain, resolve_weather_code(61, 6.0));
+        assert_eq!(Weather::Thunderstorm, resolve_weather_code(95, 0.0));
+        assert_eq!(Weather::Snow, resolve_weather_code(73, 6.0));
+    }
+}
@@ -0,0 +1,341 @@
+use super::{Location, WeatherProvider};
+use crate::air::AirQuality;
+use crate::sun::SunPosition;
+use crate::units::{Speed, Temperature};
+use crate::weather::{Conditions, Forecast, ForecastEntry, Weather, Wind};
+use crate::{Error, Result};
+use chrono::{FixedOffset, TimeZone, Utc};
+use log::debug;
+use openweather::{Language, LocationSpecifier, Settings, Unit};
+use serde_derive::Deserialize;
+
+const NAME: &str = "OpenWeatherMap";
+const FORECAST_URL: &str = "https://api.openweathermap.org/data/2.5/forecast";
+const AIR_POLLUTION_URL: &str = "https://api.openweathermap.org/data/2.5/air_pollution";
+
+/// Weather from [OpenWeatherMap](https://openweathermap.org/api), which requires an API key
+pub struct OpenWeatherMap {
+    api_key: String,
+}
+
+impl OpenWeatherMap {
+    pub fn new(api_key: String) -> Self {
+        Self { api_key }
+    }
+}
+
+impl From<&Location> for LocationSpecifier {
+    fn from(loc: &Location) -> Self {
+        match loc.clone() {
+            Location::ZipCode { zip, country } => Self::ZipCode { zip, country },
+            Location::City { name, country } => Self::CityAndCountryName {
+                city: name,
+                country,
+            },
+            Location::Coordinates { lat, lon } => Self::Coordinates { lat, lon },
+        }
+    }
+}
+
+fn location_query(loc: &Location) -> Vec<(&'static str, String)> {
+    match loc {
+        Location::ZipCode { zip, country } => vec![("zip", format!("{},{}", zip, country))],
+        Location::City { name, country } => vec![("q", format!("{},{}", name, country))],
+        Location::Coordinates { lat, lon } => {
+            vec![("lat", lat.to_string()), ("lon", lon.to_string())]
+        }
+    }
+}
+
+// Resolve weather from OpenWeatherMap weather condition codes and % cloud cover
+//
+// See https://openweathermap.org/weather-conditions for the list of codes
+fn resolve_weather(ids: impl IntoIterator<Item = u16>, cloud_cover: u8) -> Weather {
+    // Fog, haze, and the like only count if there's no precipitation
+    let mut atmosphere = None;
+    for id in ids {
+        match id {
+            // 781 is a tornado
+            200..=299 | 781 => return Weather::Thunderstorm,
+            300..=399 => return Weather::Drizzle,
+            511 => return Weather::FreezingRain,
+            500 | 501 | 520 => return Weather::Rain,
+            500..=599 => return Weather::HeavyRain,
+            // Sleet, and mixed rain and snow
+            611..=616 => return Weather::Sleet,
+            600..=699 => return Weather::Snow,
+            // Mist and fog
+            701 | 741 => atmosphere = atmosphere.or(Some(Weather::Fog)),
+            // Smoke, haze, dust, sand, and volcanic ash
+            711..=762 => atmosphere = atmosphere.or(Some(Weather::Haze)),
+            _ => (),
+        }
+    }
+    if let Some(weather) = atmosphere {
+        return weather;
+    }
+    // No precipitation. Can use % cloud cover to determine weather
+    if cloud_cover > 75 {
+        Weather::Overcast
+    } else if cloud_cover > 25 {
+        Weather::PartlyCloudy
+    } else {
+        Weather::Clear
+    }
+}
+
+// Subset of OpenWeatherMap's 5 day/3 hour forecast response
+#[derive(Deserialize)]
+struct OwmForecast {
+    list: Vec<OwmForecastItem>,
+    city: OwmCity,
+}
+
+#[derive(Deserialize)]
+struct OwmCity {
+    /// Offset from UTC in seconds
+    timezone: i32,
+    coord: OwmCoord,
+}
+
+#[derive(Deserialize)]
+struct OwmCoord {
+    lat: f32,
+    lon: f32,
+}
+
+#[derive(Deserialize)]
+struct OwmForecastItem {
+    dt: i64,
+    main: OwmMain,
+    weather: Vec<OwmWeather>,
+    clouds: OwmClouds,
+    wind: OwmWind,
+}
+
+#[derive(Deserialize)]
+struct OwmMain {
+    temp: f32,
+    humidity: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct OwmWeather {
+    id: u16,
+}
+
+#[derive(Deserialize)]
+struct OwmClouds {
+    all: u8,
+}
+
+#[derive(Deserialize)]
+struct OwmWind {
+    speed: f32,
+    gust: Option<f32>,
+}
+
+// Subset of OpenWeatherMap's current air pollution response
+#[derive(Deserialize)]
+struct OwmAirPollution {
+    list: Vec<OwmAirPollutionItem>,
+}
+
+#[derive(Deserialize)]
+struct OwmAirPollutionItem {
+    components: OwmComponents,
+}
+
+// Concentrations in µg/m³
+#[derive(Deserialize)]
+struct OwmComponents {
+    pm2_5: f32,
+}
+
+impl OwmAirPollution {
+    fn air_quality(&self) -> Option<AirQuality> {
+        // OpenWeatherMap has its own 1-5 index, so work out the US one from PM2.5 instead
+        let item = self.list.first()?;
+        Some(AirQuality::from_pm2_5(item.components.pm2_5))
+    }
+}
+
+impl From<OwmForecast> for Forecast {
+    fn from(forecast: OwmForecast) -> Self {
+        let city = &forecast.city;
+        let entries = forecast
+            .list
+            .iter()
+            .filter_map(|item| {
+                let time = Utc.timestamp_opt(item.dt, 0).single()?;
+                let wind_speed = Speed::from_meters_per_second(item.wind.speed);
+                let sun = SunPosition::new(city.coord.lat, city.coord.lon, time);
+                let conditions = Conditions {
+                    temperature: Temperature::from_celsius(item.main.temp),
+                    weather: resolve_weather(
+                        item.weather.iter().map(|weather| weather.id),
+                        item.clouds.all,
+                    ),
+                    wind: Wind::from_speed(wind_speed),
+                    time: sun.time_of_day(),
+                    sun_elevation: Some(sun.elevation),
+                    humidity: item.main.humidity,
+                    wind_speed: Some(wind_speed),
+                    wind_gust: item.wind.gust.map(Speed::from_meters_per_second),
+                    ..Conditions::default()
+                };
+                // Skip entries that don't make sense rather than failing the whole forecast
+                conditions.validate().ok()?;
+                Some(ForecastEntry { time, conditions })
+            })
+            .collect();
+        Self {
+            utc_offset: FixedOffset::east_opt(city.timezone)
+                .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap()),
+            entries,
+            issued_at: None,
+        }
+    }
+}
+
+impl WeatherProvider for OpenWeatherMap {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn current(&self, loc: &Location) -> Result<Conditions> {
+        debug!("Getting weather for {}", loc);
+
+        let settings: Settings = Settings {
+            unit: Some(Unit::Metric),
+            lang: Some(Language::English),
+        };
+
+        let weather = openweather::get_current_weather(&loc.into(), &self.api_key, &settings)
+            .map_err(|e| Error::fetch_weather(NAME, e))?;
+        // Requested in metric units, so wind speed is in m/s
+        let wind_speed = Speed::from_meters_per_second(weather.wind.speed as f32);
+        let observed_at = Utc.timestamp_opt(weather.dt as i64, 0).single();
+        let mut conditions: Conditions = Conditions {
+            temperature: Temperature::from_celsius(weather.main.temp as f32),
+            wind: Wind::from_speed(wind_speed),
+            weather: resolve_weather(
+                weather.weather.iter().map(|item| item.id as u16),
+                weather.clouds.all as u8,
+            ),
+            humidity: Some(weather.main.humidity as f32),
+            wind_speed: Some(wind_speed),
+            observed_at,
+            ..Conditions::default()
+        };
+        if let Some(observed_at) = observed_at {
+            let (lat, lon) = (weather.coord.lat as f32, weather.coord.lon as f32);
+            conditions.set_sun_position(SunPosition::new(lat, lon, observed_at));
+        }
+        conditions.validate()?;
+        Ok(conditions)
+    }
+
+    fn forecast(&self, loc: &Location) -> Result<Forecast> {
+        debug!("Getting forecast for {}", loc);
+
+        let mut query = location_query(loc);
+        query.push(("units", "metric".to_string()));
+        query.push(("appid", self.api_key.clone()));
+        let forecast: OwmForecast = reqwest::blocking::Client::new()
+            .get(FORECAST_URL)
+            .query(&query)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .map_err(|e| Error::fetch_weather(NAME, e))?;
+        Ok(Forecast {
+            issued_at: Some(Utc::now()),
+            ..forecast.into()
+        })
+    }
+
+    fn air_quality(&self, loc: &Location) -> Result<Option<AirQuality>> {
+        debug!("Getting air quality for {}", loc);
+
+        // Air pollution can only be looked up by coordinates, which the current weather has
+        let (lat, lon) = match loc.coordinates() {
+            Some(coordinates) => coordinates,
+            None => {
+                let settings = Settings {
+                    unit: Some(Unit::Metric),
+                    lang: Some(Language::English),
+                };
+                let weather =
+                    openweather::get_current_weather(&loc.into(), &self.api_key, &settings)
+                        .map_err(|e| Error::fetch_weather(NAME, e))?;
+                (weather.coord.lat as f32, weather.coord.lon as f32)
+            }
+        };
+        let query = [
+            ("lat", lat.to_string()),
+            ("lon", lon.to_string()),
+            ("appid", self.api_key.clone()),
+        ];
+        let air_pollution: OwmAirPollution = reqwest::blocking::Client::new()
+            .get(AIR_POLLUTION_URL)
+            .query(&query)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.json())
+            .map_err(|e| Error::fetch_weather(NAME, e))?;
+        Ok(air_pollution.air_quality())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::TimeOfDay;
+    use chrono::NaiveDateTime;
+
+    #[test]
+    fn test_forecast() {
+        let forecast: OwmForecast =
+            serde_json::from_str(include_str!("../../tests/fixtures/owm_forecast.json")).unwrap();
+        let forecast = Forecast::from(forecast);
+        assert_eq!(2, forecast.entries.len());
+
+        // 3am and 6am local time, both before sunrise
+        let morning = forecast
+            .to_utc(NaiveDateTime::parse_from_str("2020-10-18 06:00", "%Y-%m-%d %H:%M").unwrap());
+        let conditions = forecast.at(morning).unwrap();
+        assert_eq!(Weather::Rain, conditions.weather);
+        assert_eq!(Wind::Light, conditions.wind);
+        assert_eq!(
+            Some(Speed::from_meters_per_second(11.2)),
+            conditions.wind_gust
+        );
+        assert_eq!(TimeOfDay::Night, conditions.time);
+        assert_eq!(-12.0, conditions.sun_elevation.unwrap().round());
+        assert_eq!(TimeOfDay::Night, forecast.entries[0].conditions.time);
+        assert!(forecast.at(morning + chrono::Duration::days(1)).is_err());
+    }
+
+    #[test]
+    fn test_air_pollution() {
+        let air_pollution: OwmAirPollution =
+            serde_json::from_str(include_str!("../../tests/fixtures/owm_air_pollution.json"))
+                .unwrap();
+        let air_quality = air_pollution.air_quality().unwrap();
+        assert_eq!(Some(62.1), air_quality.pm2_5);
+        assert_eq!(156, air_quality.aqi);
+    }
+
+    #[test]
+    fn test_resolve_weather() {
+        assert_eq!(Weather::Thunderstorm, resolve_weather(vec![211], 90));
+        assert_eq!(Weather::Drizzle, resolve_weather(vec![300], 90));
+        assert_eq!(Weather::FreezingRain, resolve_weather(vec![511], 90));
+        assert_eq!(Weather::Sleet, resolve_weather(vec![616], 90));
+        // Precipitation takes priority over mist
+        assert_eq!(Weather::Rain, resolve_weather(vec![701, 500], 90));
+        assert_eq!(Weather::Haze, resolve_weather(vec![711], 0));
+        assert_eq!(Weather::PartlyCloudy, resolve_weather(vec![802], 40));
+    }
+}
@@ -0,0 +1,34 @@
+//! Local HTTP server that serves recorded API responses, so that providers can be tested without a
+//! network connection
+use std::collections::HashMap;
+use std::thread;
+use tiny_http::{Response, Server};
+
+pub struct TestServer {
+    pub base_url: String,
+}
+
+impl TestServer {
+    /// Serve each body at its path. Occurrences of `recorded_base_url` in the bodies are replaced
+    /// with the server's own URL so that links between responses keep working.
+    pub fn start(recorded_base_url: &str, routes: &[(&str, &str)]) -> Self {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", server.server_addr());
+        let routes: HashMap<String, String> = routes
+            .iter()
+            .map(|(path, body)| (path.to_string(), body.replace(recorded_base_url, &base_url)))
+            .collect();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                // Ignore query strings
+                let path = request.url().split('?').next().unwrap_or_default();
+                let response = match routes.get(path) {
+                    Some(body) => Response::from_string(body.as_str()),
+                    None => Response::from_string("Not found").with_status_code(404),
+                };
+                request.respond(response).ok();
+            }
+        });
+        Self { base_url }
+    }
+}
@@ -0,0 +1,391 @@
+//! A small predicate language used to decide whether gear is appropriate beyond its temperature
+//! range
+//!
+//! Rules are serializable so they can live in a gear catalog, e.g. in TOML:
+//!
+//! ```toml
+//! rule = { any = [{ sex = "female" }, { temperature = { max = 80 } }] }
+//! ```
+use crate::inputs::{Feel, Intensity, RunParameters, Sex};
+use crate::sun::{Daylight, UvCategory};
+use crate::units::{DisplayUnits, RunDuration, Temperature, Units};
+use crate::weather::{TimeOfDay, Weather, Wind};
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RuleTable", into = "RuleTable")]
+pub enum Rule {
+    /// True if every inner rule is true
+    All(Vec<Rule>),
+    /// True if at least one inner rule is true
+    Any(Vec<Rule>),
+    Not(Box<Rule>),
+    /// True if the current weather is one of the listed options
+    Weather(Vec<Weather>),
+    /// True if the current time of day is one of the listed options
+    Time(Vec<TimeOfDay>),
+    /// True if how light it is out is one of the listed options
+    Daylight(Vec<Daylight>),
+    /// True if the current wind is one of the listed options
+    Wind(Vec<Wind>),
+    /// True if the UV exposure category is one of the listed options
+    Uv(Vec<UvCategory>),
+    /// True if the run intensity is one of the listed options
+    Intensity(Vec<Intensity>),
+    Sex(Sex),
+    /// True if the runner's feel is one of the listed options
+    Feel(Vec<Feel>),
+    /// True if the effective temperature is within the given inclusive bounds
+    Temperature {
+        min: Option<Temperature>,
+        max: Option<Temperature>,
+    },
+    /// True if the run's duration is known and within the given inclusive bounds
+    Duration {
+        min: Option<RunDuration>,
+        max: Option<RunDuration>,
+    },
+}
+
+// Inclusive bounds, either of which may be left out
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Bounds<T> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max: Option<T>,
+}
+
+// How a rule is written: a table with a single key for the kind of rule, e.g. `{ sex = "female" }`.
+// Unlike serde's usual representation of enums, this can be written out as TOML and read back in
+// whether or not the table is inline.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleTable {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    all: Option<Vec<Rule>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    any: Option<Vec<Rule>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    not: Option<Box<Rule>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weather: Option<Vec<Weather>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<Vec<TimeOfDay>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    daylight: Option<Vec<Daylight>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wind: Option<Vec<Wind>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uv: Option<Vec<UvCategory>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    intensity: Option<Vec<Intensity>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sex: Option<Sex>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feel: Option<Vec<Feel>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<Bounds<Temperature>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<Bounds<RunDuration>>,
+}
+
+impl TryFrom<RuleTable> for Rule {
+    type Error = String;
+
+    fn try_from(table: RuleTable) -> Result<Self, Self::Error> {
+        let RuleTable {
+            all,
+            any,
+            not,
+            weather,
+            time,
+            daylight,
+            wind,
+            uv,
+            intensity,
+            sex,
+            feel,
+            temperature,
+            duration,
+        } = table;
+        let mut rules: Vec<Self> = Vec::new();
+        rules.extend(all.map(Self::All));
+        rules.extend(any.map(Self::Any));
+        rules.extend(not.map(Self::Not));
+        rules.extend(weather.map(Self::Weather));
+        rules.extend(time.map(Self::Time));
+        rules.extend(daylight.map(Self::Daylight));
+        rules.extend(wind.map(Self::Wind));
+        rules.extend(uv.map(Self::Uv));
+        rules.extend(intensity.map(Self::Intensity));
+        rules.extend(sex.map(Self::Sex));
+        rules.extend(feel.map(Self::Feel));
+        rules.extend(temperature.map(|Bounds { min, max }| Self::Temperature { min, max }));
+        rules.extend(duration.map(|Bounds { min, max }| Self::Duration { min, max }));
+        match (rules.pop(), rules.is_empty()) {
+            (Some(rule), true) => Ok(rule),
+            _ => Err("A rule must have exactly one key, e.g. { sex = \"female\" }".to_string()),
+        }
+    }
+}
+
+impl From<Rule> for RuleTable {
+    fn from(rule: Rule) -> Self {
+        match rule {
+            Rule::All(rules) => Self {
+                all: Some(rules),
+                ..Self::default()
+            },
+            Rule::Any(rules) => Self {
+                any: Some(rules),
+                ..Self::default()
+            },
+            Rule::Not(rule) => Self {
+                not: Some(rule),
+                ..Self::default()
+            },
+            Rule::Weather(options) => Self {
+                weather: Some(options),
+                ..Self::default()
+            },
+            Rule::Time(options) => Self {
+                time: Some(options),
+                ..Self::default()
+            },
+            Rule::Daylight(options) => Self {
+                daylight: Some(options),
+                ..Self::default()
+            },
+            Rule::Wind(options) => Self {
+                wind: Some(options),
+                ..Self::default()
+            },
+            Rule::Uv(options) => Self {
+                uv: Some(options),
+                ..Self::default()
+            },
+            Rule::Intensity(options) => Self {
+                intensity: Some(options),
+                ..Self::default()
+            },
+            Rule::Sex(sex) => Self {
+                sex: Some(sex),
+                ..Self::default()
+            },
+            Rule::Feel(options) => Self {
+                feel: Some(options),
+                ..Self::default()
+            },
+            Rule::Temperature { min, max } => Self {
+                temperature: Some(Bounds { min, max }),
+                ..Self::default()
+            },
+            Rule::Duration { min, max } => Self {
+                duration: Some(Bounds { min, max }),
+                ..Self::default()
+            },
+        }
+    }
+}
+
+impl Rule {
+    pub fn evaluate(&self, params: &RunParameters) -> bool {
+        match self {
+            Self::All(rules) => rules.iter().all(|rule| rule.evaluate(params)),
+            Self::Any(rules) => rules.iter().any(|rule| rule.evaluate(params)),
+            Self::Not(rule) => !rule.evaluate(params),
+            Self::Weather(options) => options.contains(&params.conditions.weather),
+            Self::Time(options) => options.contains(&params.conditions.time),
+            Self::Daylight(options) => options.contains(&params.conditions.daylight()),
+            Self::Wind(options) => options.contains(&params.conditions.wind),
+            Self::Uv(options) => options.contains(&params.conditions.uv_category()),
+            Self::Intensity(options) => options.contains(&params.preferences.intensity),
+            Self::Sex(sex) => *sex == params.preferences.sex,
+            Self::Feel(options) => options.contains(&params.preferences.feel),
+            Self::Temperature { min, max } => {
+                let temperature = params.effective_temperature();
+                min.iter().all(|&min| temperature >= min)
+                    && max.iter().all(|&max| temperature <= max)
+            }
+            Self::Duration { min, max } => params.preferences.duration.is_some_and(|duration| {
+                min.iter().all(|&min| duration >= min) && max.iter().all(|&max| duration <= max)
+            }),
+        }
+    }
+
+    /// Returns the most specific part of this rule that causes it to fail, or `None` if the rule
+    /// passes
+    pub fn find_failure(&self, params: &RunParameters) -> Option<&Self> {
+        match self {
+            Self::All(rules) => rules.iter().find_map(|rule| rule.find_failure(params)),
+            _ if self.evaluate(params) => None,
+            _ => Some(self),
+        }
+    }
+
+    const fn is_compound(&self) -> bool {
+        matches!(self, Self::All(_) | Self::Any(_))
+    }
+}
+
+// Join items into a list of alternatives, e.g. "rain or heavy rain"
+fn join_options<T: fmt::Display>(options: &[T]) -> String {
+    options
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<String>>()
+        .join(" or ")
+}
+
+fn fmt_compound(
+    f: &mut fmt::Formatter,
+    rules: &[Rule],
+    separator: &str,
+    units: Units,
+) -> fmt::Result {
+    for (i, rule) in rules.iter().enumerate() {
+        if i > 0 {
+            write!(f, "{}", separator)?;
+        }
+        if rule.is_compound() {
+            write!(f, "({})", rule.in_units(units))?;
+        } else {
+            write!(f, "{}", rule.in_units(units))?;
+        }
+    }
+    Ok(())
+}
+
+impl DisplayUnits for Rule {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result {
+        match self {
+            Self::All(rules) if rules.is_empty() => write!(f, "always"),
+            Self::All(rules) => fmt_compound(f, rules, " and ", units),
+            Self::Any(rules) if rules.is_empty() => write!(f, "never"),
+            Self::Any(rules) => fmt_compound(f, rules, " or ", units),
+            Self::Not(rule) => write!(f, "not ({})", rule.in_units(units)),
+            Self::Weather(options) => write!(f, "weather is {}", join_options(options)),
+            Self::Time(options) => write!(f, "time of day is {}", join_options(options)),
+            Self::Daylight(options) => write!(f, "it's {} out", join_options(options)),
+            Self::Wind(options) => write!(f, "wind is {}", join_options(options)),
+            Self::Uv(options) => write!(f, "UV is {}", join_options(options)),
+            Self::Intensity(options) => write!(f, "intensity is {}", join_options(options)),
+            Self::Sex(sex) => write!(f, "runner is {}", sex),
+            Self::Feel(options) => write!(f, "runner feel is {}", join_options(options)),
+            Self::Temperature {
+                min: Some(min),
+                max: Some(max),
+            } => write!(
+                f,
+                "temperature is between {} and {}",
+                min.in_units(units),
+                max.in_units(units)
+            ),
+            Self::Temperature {
+                min: Some(min),
+                max: None,
+            } => write!(f, "temperature is at least {}", min.in_units(units)),
+            Self::Temperature {
+                min: None,
+                max: Some(max),
+            } => write!(f, "temperature is at most {}", max.in_units(units)),
+            Self::Temperature {
+                min: None,
+                max: None,
+            } => write!(f, "any temperature"),
+            Self::Duration {
+                min: Some(min),
+                max: Some(max),
+            } => write!(f, "run lasts between {} and {}", min, max),
+            Self::Duration {
+                min: Some(min),
+                max: None,
+            } => write!(f, "run lasts at least {}", min),
+            Self::Duration {
+                min: None,
+                max: Some(max),
+            } => write!(f, "run lasts at most {}", max),
+            Self::Duration {
+                min: None,
+                max: None,
+            } => write!(f, "run length is known"),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_units(f, Units::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inputs::UserPreferences;
+    use crate::weather::Conditions;
+
+    #[test]
+    fn test_evaluate() {
+        let conditions = Conditions {
+            temperature: Temperature::from_fahrenheit(50.0),
+            weather: Weather::Rain,
+            ..Conditions::default()
+        };
+        let preferences = UserPreferences {
+            intensity: Intensity::Race,
+            ..UserPreferences::default()
+        };
+        let params = RunParameters::new(&conditions, &preferences);
+
+        assert!(Rule::Weather(vec![Weather::Rain, Weather::HeavyRain]).evaluate(&params));
+        assert!(!Rule::Not(Box::new(Rule::Intensity(vec![Intensity::Race]))).evaluate(&params));
+        assert!(
+            Rule::Any(vec![Rule::Sex(Sex::Female), Rule::Wind(vec![Wind::Calm])]).evaluate(&params)
+        );
+        // Rain blocks most of the sun
+        assert!(Rule::Uv(vec![UvCategory::Low]).evaluate(&params));
+        // Nothing is known about how long the run is
+        assert!(!Rule::Duration {
+            min: None,
+            max: None
+        }
+        .evaluate(&params));
+        assert!(!Rule::All(vec![
+            Rule::Sex(Sex::Male),
+            Rule::Temperature {
+                min: None,
+                max: Some(Temperature::from_fahrenheit(40.0))
+            }
+        ])
+        .evaluate(&params));
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            rule: Rule,
+        }
+        let wrapper: Wrapper = toml::from_str(
+            r#"rule = { any = [{ sex = "female" }, { not = { weather = ["rain", "heavy_rain"] } }] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            "runner is female or not (weather is rain or heavy rain)",
+            wrapper.rule.to_string()
+        );
+        let wrapper: Wrapper = toml::from_str(r#"rule = { uv = ["high", "very_high"] }"#).unwrap();
+        assert_eq!("UV is high or very high", wrapper.rule.to_string());
+        let wrapper: Wrapper = toml::from_str(r#"rule = { duration = { min = 90 } }"#).unwrap();
+        assert_eq!("run lasts at least 1h30m", wrapper.rule.to_string());
+        assert!(
+            toml::from_str::<Wrapper>(r#"rule = { sex = "female", feel = ["runs_warm"] }"#)
+                .is_err()
+        );
+    }
+}
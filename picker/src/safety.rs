@@ -0,0 +1,398 @@
+//! Dangers to look out for on a run, beyond what to wear
+//!
+//! Warnings are kept separate from the outfit since no amount of gear makes some conditions safe.
+use crate::air::AqiCategory;
+use crate::humidity::{heat_index, HEAT_INDEX_MIN};
+use crate::inputs::RunParameters;
+use crate::units::{DisplayUnits, Units};
+use crate::weather::{wind_chill, TimeOfDay, Weather};
+use serde_derive::Serialize;
+use std::cmp::Reverse;
+use std::fmt;
+
+// Heat index thresholds in °F, roughly following the NWS's heat index categories
+const HEAT_CAUTION: f32 = 90.0;
+const HEAT_WARNING: f32 = 103.0;
+const HEAT_DANGER: f32 = 125.0;
+// Runs at least this many minutes long are risky in the heat even when shorter ones aren't
+const LONG_HEAT_MINUTES: u16 = 60;
+
+// Wind chill in °F at or below which exposed skin gets frostbite within the given number of
+// minutes, from the NWS wind chill chart
+const FROSTBITE_TIMES: [(f32, u8); 3] = [(-48.0, 5), (-33.0, 10), (-18.0, 30)];
+// Wind chill in °F at or below which exposed skin should be covered up
+const FROSTBITE_CAUTION: f32 = 0.0;
+
+// Temperature in °F at or below which wet surfaces might freeze
+const ICE_POSSIBLE: f32 = 34.0;
+
+/// How much a warning should change plans
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Worth knowing about, but fine with some care
+    Caution,
+    /// Consider changing or cutting the run short
+    Warning,
+    /// Don't run outside
+    Danger,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Caution => "Caution",
+            Self::Warning => "Warning",
+            Self::Danger => "Danger",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// What a warning is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hazard {
+    /// Heat exhaustion or heat stroke
+    HeatIllness,
+    Frostbite,
+    Lightning,
+    /// Trouble seeing, or being seen by drivers
+    LowVisibility,
+    /// Icy or snowy roads and sidewalks
+    SlipperySurfaces,
+    /// Smoke, dust, or other pollution
+    AirQuality,
+}
+
+/// Something dangerous about running in the given conditions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Warning {
+    pub hazard: Hazard,
+    pub severity: Severity,
+    /// What the danger is and what to do about it
+    pub message: String,
+}
+
+impl Warning {
+    fn new(hazard: Hazard, severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            hazard,
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+fn heat_illness(params: &RunParameters, units: Units) -> Option<Warning> {
+    let conditions = &params.conditions;
+    if conditions.temperature < HEAT_INDEX_MIN {
+        return None;
+    }
+    // Without the humidity, the temperature alone is the best guess
+    let humid_index = conditions
+        .relative_humidity()
+        .map(|humidity| heat_index(conditions.temperature, humidity));
+    let index = humid_index.unwrap_or(conditions.temperature);
+    let fahrenheit = index.fahrenheit();
+    let severity = if fahrenheit >= HEAT_DANGER {
+        Severity::Danger
+    } else if fahrenheit >= HEAT_WARNING {
+        Severity::Warning
+    } else if fahrenheit >= HEAT_CAUTION {
+        let long_run = params
+            .preferences
+            .duration
+            .is_some_and(|duration| duration.minutes() >= LONG_HEAT_MINUTES);
+        if long_run {
+            Severity::Warning
+        } else {
+            Severity::Caution
+        }
+    } else {
+        return None;
+    };
+    let advice = match severity {
+        Severity::Danger => "Heat stroke is likely, so run indoors or wait until it cools off.",
+        Severity::Warning => {
+            "Heat exhaustion is likely. Keep it short and easy, drink plenty, and stop if you feel \
+            dizzy or sick."
+        }
+        Severity::Caution => "Slow down, drink plenty, and take breaks in the shade.",
+    };
+    Some(Warning::new(
+        Hazard::HeatIllness,
+        severity,
+        match humid_index {
+            Some(_) => format!(
+                "It feels like {} with the humidity. {}",
+                index.in_units(units),
+                advice
+            ),
+            None => format!("It's {} out. {}", index.in_units(units), advice),
+        },
+    ))
+}
+
+fn frostbite(params: &RunParameters, units: Units) -> Option<Warning> {
+    let conditions = &params.conditions;
+    let chill = wind_chill(conditions.temperature, conditions.estimated_wind_speed());
+    let fahrenheit = chill.fahrenheit();
+    if let Some(&(_, minutes)) = FROSTBITE_TIMES
+        .iter()
+        .find(|&&(threshold, _)| fahrenheit <= threshold)
+    {
+        let severity = if minutes <= 10 {
+            Severity::Danger
+        } else {
+            Severity::Warning
+        };
+        return Some(Warning::new(
+            Hazard::Frostbite,
+            severity,
+            format!(
+                "With a wind chill of {}, exposed skin can get frostbite in {} minutes. Cover \
+                everything, or run indoors.",
+                chill.in_units(units),
+                minutes
+            ),
+        ));
+    }
+    if fahrenheit <= FROSTBITE_CAUTION {
+        return Some(Warning::new(
+            Hazard::Frostbite,
+            Severity::Caution,
+            format!(
+                "With a wind chill of {}, cover exposed skin and watch for numbness.",
+                chill.in_units(units)
+            ),
+        ));
+    }
+    None
+}
+
+fn lightning(params: &RunParameters) -> Option<Warning> {
+    match params.conditions.weather {
+        Weather::Thunderstorm => Some(Warning::new(
+            Hazard::Lightning,
+            Severity::Danger,
+            "Lightning is likely. Wait until 30 minutes after the last thunder, or run indoors.",
+        )),
+        _ => None,
+    }
+}
+
+fn low_visibility(params: &RunParameters) -> Option<Warning> {
+    let conditions = &params.conditions;
+    let dark = conditions.time == TimeOfDay::Night;
+    let obscured = matches!(
+        conditions.weather,
+        Weather::Fog | Weather::HeavyRain | Weather::Snow
+    );
+    match (dark, obscured) {
+        (true, true) => Some(Warning::new(
+            Hazard::LowVisibility,
+            Severity::Warning,
+            "It's dark and visibility is poor, so drivers may not see you. Wear lights and \
+            reflective gear, and stick to paths away from traffic.",
+        )),
+        (true, false) => Some(Warning::new(
+            Hazard::LowVisibility,
+            Severity::Caution,
+            "It's dark out. Wear lights and reflective gear so drivers can see you.",
+        )),
+        (false, true) => Some(Warning::new(
+            Hazard::LowVisibility,
+            Severity::Caution,
+            "Visibility is poor. Wear something bright and watch for cars.",
+        )),
+        (false, false) => None,
+    }
+}
+
+fn slippery_surfaces(params: &RunParameters) -> Option<Warning> {
+    let conditions = &params.conditions;
+    let near_freezing = conditions.temperature.fahrenheit() <= ICE_POSSIBLE;
+    match conditions.weather {
+        Weather::FreezingRain => Some(Warning::new(
+            Hazard::SlipperySurfaces,
+            Severity::Warning,
+            "Freezing rain makes for glazed, icy roads and sidewalks. Run indoors, or slow down and \
+            wear traction devices.",
+        )),
+        Weather::Sleet => Some(Warning::new(
+            Hazard::SlipperySurfaces,
+            Severity::Caution,
+            "Roads and sidewalks may be icy. Slow down and watch your footing.",
+        )),
+        Weather::Snow => Some(Warning::new(
+            Hazard::SlipperySurfaces,
+            Severity::Caution,
+            "Snow makes for slippery footing. Shorten your stride and take corners slowly.",
+        )),
+        Weather::Drizzle | Weather::Rain | Weather::HeavyRain if near_freezing => {
+            Some(Warning::new(
+                Hazard::SlipperySurfaces,
+                Severity::Caution,
+                "It's cold enough for wet surfaces to freeze. Watch for black ice.",
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn air_quality(params: &RunParameters) -> Option<Warning> {
+    let air_quality = match params.air_quality {
+        Some(air_quality) => air_quality,
+        // Without a reading, haze is the only hint
+        None if params.conditions.weather == Weather::Haze => {
+            return Some(Warning::new(
+                Hazard::AirQuality,
+                Severity::Caution,
+                "The air may be smoky or dusty. Consider an easier run.",
+            ))
+        }
+        None => return None,
+    };
+    let severity = match air_quality.category() {
+        AqiCategory::Good => return None,
+        AqiCategory::Moderate | AqiCategory::UnhealthyForSensitiveGroups => Severity::Caution,
+        AqiCategory::Unhealthy => Severity::Warning,
+        AqiCategory::VeryUnhealthy | AqiCategory::Hazardous => Severity::Danger,
+    };
+    Some(Warning::new(
+        Hazard::AirQuality,
+        severity,
+        air_quality.advisory()?,
+    ))
+}
+
+/// Returns warnings about anything dangerous in the run's conditions, most severe first
+pub fn assess(params: &RunParameters) -> Vec<Warning> {
+    let units = params.preferences.units;
+    let mut warnings: Vec<Warning> = vec![
+        lightning(params),
+        heat_illness(params, units),
+        frostbite(params, units),
+        air_quality(params),
+        slippery_surfaces(params),
+        low_visibility(params),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    // Stable, so ties keep the order above
+    warnings.sort_by_key(|warning| Reverse(warning.severity));
+    warnings
+}
+
+/// The most severe level of the given warnings, if any
+pub fn max_severity(warnings: &[Warning]) -> Option<Severity> {
+    warnings.iter().map(|warning| warning.severity).max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::air::AirQuality;
+    use crate::inputs::UserPreferences;
+    use crate::units::{RunDuration, Speed, Temperature};
+    use crate::weather::Conditions;
+
+    // Warnings for a run at the given temperature, with any other changes applied
+    fn assess_at(
+        fahrenheit: f32,
+        change: impl FnOnce(&mut Conditions, &mut UserPreferences),
+    ) -> Vec<Warning> {
+        let mut conditions = Conditions {
+            temperature: Temperature::from_fahrenheit(fahrenheit),
+            ..Conditions::default()
+        };
+        let mut preferences = UserPreferences::default();
+        change(&mut conditions, &mut preferences);
+        assess(&RunParameters::new(&conditions, &preferences))
+    }
+
+    #[test]
+    fn test_no_warnings() {
+        assert!(assess_at(60.0, |_, _| {}).is_empty());
+    }
+
+    #[test]
+    fn test_heat_illness() {
+        let heat = assess_at(96.0, |conditions, _| conditions.humidity = Some(50.0));
+        assert_eq!(Hazard::HeatIllness, heat[0].hazard);
+        assert_eq!(Severity::Warning, heat[0].severity);
+        assert!(heat[0]
+            .message
+            .starts_with("It feels like 108°F with the humidity."));
+
+        // Without the humidity, the temperature is all there is to go on
+        let dry_heat = assess_at(96.0, |_, _| {});
+        assert_eq!(Severity::Caution, dry_heat[0].severity);
+        assert!(dry_heat[0].message.starts_with("It's 96°F out."));
+
+        // Heat that's fine for a short run adds up over a long one
+        let lasting = |minutes| {
+            assess_at(90.0, |conditions, preferences| {
+                conditions.humidity = Some(40.0);
+                preferences.duration = Some(RunDuration::from_minutes(minutes));
+            })[0]
+                .severity
+        };
+        assert_eq!(Severity::Caution, lasting(30));
+        assert_eq!(Severity::Warning, lasting(120));
+    }
+
+    #[test]
+    fn test_frostbite() {
+        let cold = assess_at(-5.0, |conditions, _| {
+            conditions.wind_speed = Some(Speed::from_mph(25.0));
+        });
+        assert_eq!(Hazard::Frostbite, cold[0].hazard);
+        assert!(cold[0].message.contains("frostbite in 30 minutes"));
+    }
+
+    #[test]
+    fn test_most_severe_first() {
+        let stormy_night = assess_at(70.0, |conditions, _| {
+            conditions.weather = Weather::Thunderstorm;
+            conditions.time = TimeOfDay::Night;
+        });
+        let hazards: Vec<(Hazard, Severity)> = stormy_night
+            .iter()
+            .map(|warning| (warning.hazard, warning.severity))
+            .collect();
+        assert_eq!(
+            vec![
+                (Hazard::Lightning, Severity::Danger),
+                (Hazard::LowVisibility, Severity::Caution)
+            ],
+            hazards
+        );
+        assert_eq!(Some(Severity::Danger), max_severity(&stormy_night));
+    }
+
+    #[test]
+    fn test_air_quality() {
+        let conditions = Conditions {
+            temperature: Temperature::from_fahrenheit(75.0),
+            ..Conditions::default()
+        };
+        let breathing = |pm2_5| {
+            let params = RunParameters::new(&conditions, &UserPreferences::default())
+                .with_air_quality(Some(AirQuality::from_pm2_5(pm2_5)));
+            let warning = assess(&params).remove(0);
+            (warning.hazard, warning.severity)
+        };
+        assert_eq!((Hazard::AirQuality, Severity::Danger), breathing(150.0));
+        // Moderate air is only worth a heads up
+        assert_eq!((Hazard::AirQuality, Severity::Caution), breathing(20.0));
+    }
+}
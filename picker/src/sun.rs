@@ -0,0 +1,217 @@
+//! Where the sun is in the sky, and how much that warms a runner up
+//!
+//! Positions use the low-precision formulas from the
+//! [Astronomical Almanac](https://aa.usno.navy.mil/faq/sun_approx), which are good to within a
+//! degree or so. That's plenty for deciding what to wear.
+use crate::weather::TimeOfDay;
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// Elevation in degrees below which it's dark out, i.e. after civil twilight
+pub const TWILIGHT_ELEVATION: f32 = -6.0;
+/// Elevation in degrees below which the sun is low in the sky, as in the morning and evening
+pub const LOW_SUN_ELEVATION: f32 = 15.0;
+// Elevation in degrees at or above which the sun is as strong as it gets for our purposes
+const HIGH_SUN_ELEVATION: f32 = 60.0;
+
+/// Position of the sun as seen from a particular place and time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunPosition {
+    /// Angle above the horizon in degrees. Negative when the sun has set.
+    pub elevation: f32,
+    /// Angle in degrees that the earth has turned since solar noon. Negative before noon.
+    pub hour_angle: f32,
+}
+
+impl SunPosition {
+    pub fn new(lat: f32, lon: f32, time: DateTime<Utc>) -> Self {
+        // Days since noon UTC on January 1, 2000
+        let days = time.timestamp() as f64 / 86400.0 - 10957.5;
+        let mean_anomaly = (357.529 + 0.985_600_28 * days).to_radians();
+        let mean_longitude = 280.459 + 0.985_647_36 * days;
+        let ecliptic_longitude =
+            (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin())
+                .to_radians();
+        let obliquity = (23.439 - 0.000_000_36 * days).to_radians();
+        let right_ascension = (obliquity.cos() * ecliptic_longitude.sin())
+            .atan2(ecliptic_longitude.cos())
+            .to_degrees();
+        let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+        // Greenwich mean sidereal time, in degrees
+        let sidereal_time = 15.0 * (18.697_374_558 + 24.065_709_824_419_08 * days);
+        let hour_angle =
+            (sidereal_time + f64::from(lon) - right_ascension + 540.0).rem_euclid(360.0) - 180.0;
+        let lat = f64::from(lat).to_radians();
+        let elevation = (lat.sin() * declination.sin()
+            + lat.cos() * declination.cos() * hour_angle.to_radians().cos())
+        .asin()
+        .to_degrees();
+        Self {
+            elevation: elevation as f32,
+            hour_angle: hour_angle as f32,
+        }
+    }
+
+    /// Whether it's morning, daytime, evening, or night, going by how high the sun is
+    pub fn time_of_day(&self) -> TimeOfDay {
+        if self.elevation < TWILIGHT_ELEVATION {
+            TimeOfDay::Night
+        } else if self.elevation >= LOW_SUN_ELEVATION {
+            TimeOfDay::Daytime
+        } else if self.hour_angle < 0.0 {
+            TimeOfDay::Morning
+        } else {
+            TimeOfDay::Evening
+        }
+    }
+}
+
+/// How light it is out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Daylight {
+    /// After civil twilight, when it's dark enough to need a light
+    Dark,
+    /// Between sunset and the end of civil twilight, or the same before sunrise
+    Twilight,
+    Day,
+}
+
+impl Daylight {
+    pub fn from_elevation(elevation: f32) -> Self {
+        if elevation < TWILIGHT_ELEVATION {
+            Self::Dark
+        } else if elevation < 0.0 {
+            Self::Twilight
+        } else {
+            Self::Day
+        }
+    }
+}
+
+impl fmt::Display for Daylight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Dark => "dark",
+            Self::Twilight => "twilight",
+            Self::Day => "day",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// How strong the sun is at the given elevation in degrees, from 0 when it's down to 1 when it's
+/// high in the sky
+pub fn intensity(elevation: f32) -> f32 {
+    let intensity = elevation.to_radians().sin() / HIGH_SUN_ELEVATION.to_radians().sin();
+    intensity.clamp(0.0, 1.0)
+}
+
+/// UV index under a clear sky with the sun at the given elevation in degrees
+///
+/// Uses the rough fit from [Madronich (2007)](https://doi.org/10.1111/j.1751-1097.2007.00078.x),
+/// which ignores ozone, altitude, and the like.
+pub fn clear_sky_uv_index(elevation: f32) -> f32 {
+    12.5 * elevation.to_radians().sin().max(0.0).powf(2.42)
+}
+
+/// [WHO exposure category](https://www.who.int/news-room/questions-and-answers/item/radiation-the-ultraviolet-(uv)-index)
+/// for a UV index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UvCategory {
+    Low,
+    Moderate,
+    High,
+    VeryHigh,
+    Extreme,
+}
+
+impl UvCategory {
+    pub fn from_index(uv_index: f32) -> Self {
+        // Categories are defined on the index rounded to a whole number
+        let uv_index = uv_index.round();
+        if uv_index < 3.0 {
+            Self::Low
+        } else if uv_index < 6.0 {
+            Self::Moderate
+        } else if uv_index < 8.0 {
+            Self::High
+        } else if uv_index < 11.0 {
+            Self::VeryHigh
+        } else {
+            Self::Extreme
+        }
+    }
+}
+
+impl fmt::Display for UvCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Low => "low",
+            Self::Moderate => "moderate",
+            Self::High => "high",
+            Self::VeryHigh => "very high",
+            Self::Extreme => "extreme",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_sun_position() {
+        let boston = |y, m, d, h, min| {
+            SunPosition::new(
+                42.3876,
+                -71.0995,
+                Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap(),
+            )
+        };
+        // Solar noon around the solstices
+        let summer = boston(2020, 6, 20, 16, 40);
+        assert_eq!(71.0, summer.elevation.round());
+        assert_eq!(TimeOfDay::Daytime, summer.time_of_day());
+        let winter = boston(2020, 12, 21, 16, 45);
+        assert_eq!(24.0, winter.elevation.round());
+        assert!(intensity(winter.elevation) < 0.5);
+        assert_eq!(1.0, intensity(summer.elevation));
+        assert!(
+            UvCategory::from_index(clear_sky_uv_index(summer.elevation)) >= UvCategory::VeryHigh
+        );
+        assert_eq!(
+            UvCategory::Low,
+            UvCategory::from_index(clear_sky_uv_index(winter.elevation))
+        );
+
+        // Around sunrise and sunset
+        assert_eq!(TimeOfDay::Night, boston(2020, 10, 18, 10, 0).time_of_day());
+        assert_eq!(
+            TimeOfDay::Morning,
+            boston(2020, 10, 18, 11, 0).time_of_day()
+        );
+        assert_eq!(
+            TimeOfDay::Evening,
+            boston(2020, 10, 18, 22, 0).time_of_day()
+        );
+        assert_eq!(0.0, intensity(boston(2020, 10, 18, 11, 0).elevation));
+        assert_eq!(
+            Daylight::Twilight,
+            Daylight::from_elevation(boston(2020, 10, 18, 11, 0).elevation)
+        );
+
+        // Polar night in Tromsø, where the sun never gets high enough for daytime
+        let tromso = SunPosition::new(
+            69.65,
+            18.96,
+            Utc.with_ymd_and_hms(2020, 12, 21, 11, 0, 0).unwrap(),
+        );
+        assert!(tromso.elevation < 0.0);
+        assert_ne!(TimeOfDay::Daytime, tromso.time_of_day());
+    }
+}
@@ -0,0 +1,431 @@
+//! How the weather changes over the course of a run, and what to bring along because of it
+use crate::gear::{GearCatalog, Outfit};
+use crate::inputs::RunParameters;
+use crate::units::{DisplayUnits, RunDuration, Temperature, Units};
+use crate::weather::{Conditions, Forecast, Weather};
+use crate::Result;
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use serde_derive::Serialize;
+use std::fmt;
+
+// Change in °F between how the start and end of a run feel that's worth dressing for
+const NOTABLE_CHANGE: f32 = 8.0;
+
+/// Whether a run gets warmer or colder from start to finish
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Steady,
+    /// Starts cold and ends warm
+    WarmsUp,
+    /// Starts warm and ends cold
+    CoolsDown,
+}
+
+/// Conditions at some point during a run
+#[derive(Debug, Clone, Serialize)]
+pub struct TrendPoint {
+    /// In the location's local time
+    pub time: DateTime<FixedOffset>,
+    pub conditions: Conditions,
+    /// Temperature adjusted for weather, humidity, and wind
+    pub feels_like: Temperature,
+}
+
+/// How conditions change over a run, and what to wear from the start versus carry
+#[derive(Debug, Serialize)]
+pub struct Trend {
+    pub start: TrendPoint,
+    pub end: TrendPoint,
+    pub change: Change,
+    /// What to wear for the whole run. Clothes are for the warmest part of the run, except for
+    /// legs and feet, which can't be changed along the way.
+    pub wear: Outfit,
+    /// Anything else needed later in the run, like a layer for when it cools off or a headlamp
+    /// for when it gets dark
+    pub carry: Vec<String>,
+    /// Anything needed at the start but not later, like a layer to take off and tie around the
+    /// waist once it warms up
+    pub shed: Vec<String>,
+    /// When and how conditions change, if it matters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+// All the items in an outfit, for comparing outfits
+fn items(outfit: &Outfit) -> Vec<&String> {
+    outfit
+        .head
+        .iter()
+        .chain(&outfit.torso)
+        .chain(&outfit.legs)
+        .chain(&outfit.feet)
+        .chain(&outfit.accessories)
+        .collect()
+}
+
+// Join items into an English list, e.g. "a vest, gloves, and a headlamp"
+fn join_items(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [item] => item.clone(),
+        [first, second] => format!("{} and {}", first, second),
+        [rest @ .., last] => format!("{}, and {}", rest.join(", "), last),
+    }
+}
+
+fn format_time(time: DateTime<FixedOffset>) -> String {
+    time.format("%-I:%M %p").to_string()
+}
+
+// Weather worth a heads up when it starts partway through a run
+const fn is_notable(weather: Weather) -> bool {
+    !matches!(
+        weather,
+        Weather::Clear | Weather::PartlyCloudy | Weather::Overcast | Weather::Haze
+    )
+}
+
+impl Trend {
+    /// Follow the forecast over a run starting at `start` in the given conditions and lasting
+    /// `duration`
+    pub fn new(
+        params: &RunParameters,
+        forecast: &Forecast,
+        catalog: &GearCatalog,
+        start: DateTime<Utc>,
+        duration: RunDuration,
+    ) -> Result<Self> {
+        let end = start + Duration::minutes(duration.minutes().into());
+        // The start, each forecast time during the run, and the end
+        let mut samples = vec![(start, params.conditions.clone())];
+        samples.extend(
+            forecast
+                .entries
+                .iter()
+                .filter(|entry| entry.time > start && entry.time < end)
+                .map(|entry| (entry.time, entry.conditions.clone())),
+        );
+        samples.push((end, forecast.at(end)?));
+
+        let mut points = Vec::with_capacity(samples.len());
+        let mut outfits = Vec::with_capacity(samples.len());
+        for (time, conditions) in samples {
+            let sample = RunParameters::new(&conditions, &params.preferences)
+                .with_air_quality(params.air_quality);
+            outfits.push(Outfit::new(&sample, catalog)?);
+            points.push(TrendPoint {
+                time: time.with_timezone(&forecast.utc_offset),
+                feels_like: sample.effective_temperature_breakdown().feels_like(),
+                conditions,
+            });
+        }
+
+        let feels_like = |i: &usize| points[*i].feels_like.fahrenheit();
+        let warmest = (0..points.len())
+            .max_by(|a, b| feels_like(a).total_cmp(&feels_like(b)))
+            .unwrap_or_default();
+        let coldest = (0..points.len())
+            .min_by(|a, b| feels_like(a).total_cmp(&feels_like(b)))
+            .unwrap_or_default();
+
+        let wear = Outfit {
+            head: outfits[warmest].head.clone(),
+            torso: outfits[warmest].torso.clone(),
+            legs: outfits[coldest].legs.clone(),
+            feet: outfits[coldest].feet.clone(),
+            accessories: outfits[warmest].accessories.clone(),
+        };
+        // Layers for the coldest part of the run, then anything else picked up along the way.
+        // Whatever's needed from the start gets shed later, and the rest gets put on later.
+        let mut carry: Vec<String> = Vec::new();
+        let mut shed: Vec<String> = Vec::new();
+        let layers = outfits[coldest]
+            .head
+            .iter()
+            .chain(&outfits[coldest].torso)
+            .chain(&outfits[coldest].accessories);
+        let extras = outfits
+            .iter()
+            .flat_map(|outfit| outfit.head.iter().chain(&outfit.accessories));
+        for item in layers.chain(extras) {
+            if items(&wear).contains(&item) || carry.contains(item) || shed.contains(item) {
+                continue;
+            }
+            if items(&outfits[0]).contains(&item) {
+                shed.push(item.clone());
+            } else {
+                carry.push(item.clone());
+            }
+        }
+
+        let (first, last) = (&points[0], &points[points.len() - 1]);
+        let difference = last.feels_like.fahrenheit() - first.feels_like.fahrenheit();
+        let change = if difference >= NOTABLE_CHANGE {
+            Change::WarmsUp
+        } else if difference <= -NOTABLE_CHANGE {
+            Change::CoolsDown
+        } else {
+            Change::Steady
+        };
+
+        // When the first item to carry is needed, and when the first item to shed isn't
+        let carry_at = outfits
+            .iter()
+            .position(|outfit| carry.iter().any(|item| items(outfit).contains(&item)))
+            .map(|i| points[i].time);
+        let shed_at = outfits
+            .iter()
+            .position(|outfit| shed.iter().any(|item| !items(outfit).contains(&item)))
+            .map(|i| points[i].time);
+        let weather_at = points
+            .iter()
+            .find(|point| {
+                point.conditions.weather != first.conditions.weather
+                    && is_notable(point.conditions.weather)
+            })
+            .map(|point| (point.conditions.weather, point.time));
+        let note = describe(
+            change,
+            first,
+            last,
+            (&carry, carry_at),
+            (&shed, shed_at),
+            weather_at,
+            params.preferences.units,
+        );
+
+        Ok(Self {
+            start: first.clone(),
+            end: last.clone(),
+            change,
+            wear,
+            carry,
+            shed,
+            note,
+        })
+    }
+}
+
+// Describe how conditions change and what to do about it
+fn describe(
+    change: Change,
+    start: &TrendPoint,
+    end: &TrendPoint,
+    (carry, carry_at): (&[String], Option<DateTime<FixedOffset>>),
+    (shed, shed_at): (&[String], Option<DateTime<FixedOffset>>),
+    weather_at: Option<(Weather, DateTime<FixedOffset>)>,
+    units: Units,
+) -> Option<String> {
+    let around = |time: Option<DateTime<FixedOffset>>| {
+        time.map(|time| format!(" around {}", format_time(time)))
+            .unwrap_or_default()
+    };
+    let mut sentences = Vec::new();
+    let summary = format!(
+        "it feels like {} at {} but {} by {}.",
+        start.feels_like.in_units(units),
+        format_time(start.time),
+        end.feels_like.in_units(units),
+        format_time(end.time)
+    );
+    match change {
+        Change::CoolsDown => sentences.push(format!("Starts warm and ends cold: {}", summary)),
+        Change::WarmsUp => sentences.push(format!("Starts cold and ends warm: {}", summary)),
+        Change::Steady => {}
+    }
+    if !shed.is_empty() {
+        sentences.push(format!(
+            "Start in {}, then take {} off{}.",
+            join_items(shed),
+            if shed.len() == 1 { "it" } else { "them" },
+            around(shed_at)
+        ));
+    }
+    if !carry.is_empty() {
+        let carry = join_items(carry);
+        sentences.push(match change {
+            Change::Steady => format!(
+                "Bring {} for when conditions change{}.",
+                carry,
+                around(carry_at)
+            ),
+            _ => format!("Carry {} to put on{}.", carry, around(carry_at)),
+        });
+    }
+    if let Some((weather, time)) = weather_at {
+        sentences.push(format!(
+            "Expect {} from around {}.",
+            weather,
+            format_time(time)
+        ));
+    }
+    if sentences.is_empty() {
+        None
+    } else {
+        Some(sentences.join(" "))
+    }
+}
+
+impl fmt::Display for Trend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.wear)?;
+        if !self.shed.is_empty() {
+            writeln!(f, "Take off later: {}", self.shed.join(", "))?;
+        }
+        if !self.carry.is_empty() {
+            writeln!(f, "Carry: {}", self.carry.join(", "))?;
+        }
+        if let Some(note) = &self.note {
+            writeln!(f, "\n{}", note)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inputs::UserPreferences;
+    use crate::weather::{ForecastEntry, TimeOfDay};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_trend() {
+        // An evening that cools off quickly after sunset
+        let forecast = Forecast {
+            utc_offset: FixedOffset::west_opt(4 * 3600).unwrap(),
+            entries: [(22, 55.0), (23, 50.0), (0, 46.0), (1, 40.0)]
+                .iter()
+                .enumerate()
+                .map(|(i, &(hour, fahrenheit))| ForecastEntry {
+                    time: Utc
+                        .with_ymd_and_hms(2020, 10, if hour < 12 { 19 } else { 18 }, hour, 0, 0)
+                        .unwrap(),
+                    conditions: Conditions {
+                        temperature: Temperature::from_fahrenheit(fahrenheit),
+                        weather: if i < 2 {
+                            Weather::Overcast
+                        } else {
+                            Weather::Rain
+                        },
+                        time: if i == 0 {
+                            TimeOfDay::Evening
+                        } else {
+                            TimeOfDay::Night
+                        },
+                        ..Conditions::default()
+                    },
+                })
+                .collect(),
+            issued_at: None,
+        };
+        let start = forecast.entries[0].time;
+        let params =
+            RunParameters::new(&forecast.entries[0].conditions, &UserPreferences::default());
+        let catalog = GearCatalog::default();
+
+        let trend = Trend::new(
+            &params,
+            &forecast,
+            &catalog,
+            start,
+            RunDuration::from_minutes(120),
+        )
+        .unwrap();
+        assert_eq!(Change::CoolsDown, trend.change);
+        assert_eq!(Temperature::from_fahrenheit(42.0), trend.end.feels_like);
+        assert_eq!(vec!["a short-sleeved shirt"], trend.wear.torso);
+        assert_eq!(vec!["shorts"], trend.wear.legs);
+        assert_eq!(
+            vec![
+                "a hat with visor",
+                "a headlamp",
+                "a long-sleeved shirt",
+                "gloves"
+            ],
+            trend.carry
+        );
+        assert_eq!(
+            Some(
+                "Starts warm and ends cold: it feels like 55°F at 6:00 PM but 42°F by 8:00 PM. \
+                Carry a hat with visor, a headlamp, a long-sleeved shirt, and gloves to put on \
+                around 7:00 PM. Expect rain from around 8:00 PM."
+                    .to_string()
+            ),
+            trend.note
+        );
+
+        // Nothing changes over a short run
+        let trend = Trend::new(
+            &params,
+            &forecast,
+            &catalog,
+            start,
+            RunDuration::from_minutes(30),
+        )
+        .unwrap();
+        assert_eq!(Change::Steady, trend.change);
+        assert!(trend.carry.is_empty());
+        assert_eq!(None, trend.note);
+    }
+
+    #[test]
+    fn test_trend_warms_up() {
+        // A morning that warms up after a dark start, then turns rainy
+        let forecast = Forecast {
+            utc_offset: FixedOffset::west_opt(4 * 3600).unwrap(),
+            entries: [(10, 36.0), (11, 46.0), (12, 58.0), (13, 54.0)]
+                .iter()
+                .enumerate()
+                .map(|(i, &(hour, fahrenheit))| ForecastEntry {
+                    time: Utc.with_ymd_and_hms(2020, 10, 18, hour, 0, 0).unwrap(),
+                    conditions: Conditions {
+                        temperature: Temperature::from_fahrenheit(fahrenheit),
+                        weather: if i < 3 { Weather::Clear } else { Weather::Rain },
+                        time: if i == 0 {
+                            TimeOfDay::Night
+                        } else {
+                            TimeOfDay::Morning
+                        },
+                        ..Conditions::default()
+                    },
+                })
+                .collect(),
+            issued_at: None,
+        };
+        let params =
+            RunParameters::new(&forecast.entries[0].conditions, &UserPreferences::default());
+        let trend = Trend::new(
+            &params,
+            &forecast,
+            &GearCatalog::default(),
+            forecast.entries[0].time,
+            RunDuration::from_minutes(180),
+        )
+        .unwrap();
+        assert_eq!(Change::WarmsUp, trend.change);
+        assert_eq!(
+            vec![
+                "a winter cap",
+                "a headlamp",
+                "a vest",
+                "a long-sleeved shirt",
+                "gloves"
+            ],
+            trend.shed
+        );
+        // Rain later on calls for something that isn't needed at the start
+        assert_eq!(vec!["a hat with visor"], trend.carry);
+        assert_eq!(
+            Some(
+                "Starts cold and ends warm: it feels like 36°F at 6:00 AM but 50°F by 9:00 AM. \
+                Start in a winter cap, a headlamp, a vest, a long-sleeved shirt, and gloves, then \
+                take them off around 7:00 AM. Carry a hat with visor to put on around 9:00 AM. \
+                Expect rain from around 9:00 AM."
+                    .to_string()
+            ),
+            trend.note
+        );
+    }
+}
@@ -0,0 +1,602 @@
+//! Typed temperatures, speeds, distances, and durations, and the user's preferred units for
+//! displaying them
+use crate::inputs::parse_choice;
+use crate::{Error, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    /// °F and mph
+    Imperial,
+    /// °C and km/h
+    Metric,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self::Imperial
+    }
+}
+
+impl FromStr for Units {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_choice(
+            "unit system",
+            s,
+            &[("imperial", Self::Imperial), ("metric", Self::Metric)],
+        )
+    }
+}
+
+/// Formatting that depends on the user's preferred units
+pub trait DisplayUnits {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result;
+
+    /// Wrap `self` so that it can be displayed in the given units
+    fn in_units(&self, units: Units) -> InUnits<'_, Self> {
+        InUnits(self, units)
+    }
+}
+
+pub struct InUnits<'a, T: ?Sized>(&'a T, Units);
+
+impl<T: DisplayUnits + ?Sized> fmt::Display for InUnits<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_units(f, self.1)
+    }
+}
+
+// Round to a whole number for display. Converting to an integer also avoids printing "-0".
+fn round(value: f32) -> i32 {
+    value.round() as i32
+}
+
+/// A temperature, independent of any particular unit
+///
+/// Deserializes from either a number, interpreted as °F, or a string with a unit suffix, e.g.
+/// `"38F"`, `"3.5°C"`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(try_from = "NumberOrString", into = "String")]
+pub struct Temperature {
+    // Stored in °F since that's what the gear thresholds were originally written in
+    fahrenheit: f32,
+}
+
+impl Temperature {
+    pub const fn from_fahrenheit(fahrenheit: f32) -> Self {
+        Self { fahrenheit }
+    }
+
+    pub fn from_celsius(celsius: f32) -> Self {
+        Self::from_fahrenheit(celsius * 9.0 / 5.0 + 32.0)
+    }
+
+    pub const fn fahrenheit(self) -> f32 {
+        self.fahrenheit
+    }
+
+    pub fn celsius(self) -> f32 {
+        (self.fahrenheit - 32.0) * 5.0 / 9.0
+    }
+
+    /// Value in the given units, rounded to the nearest degree
+    pub fn round(self, units: Units) -> i32 {
+        match units {
+            Units::Imperial => round(self.fahrenheit()),
+            Units::Metric => round(self.celsius()),
+        }
+    }
+}
+
+impl DisplayUnits for Temperature {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result {
+        match units {
+            Units::Imperial => write!(f, "{}°F", self.round(units)),
+            Units::Metric => write!(f, "{}°C", self.round(units)),
+        }
+    }
+}
+
+impl fmt::Display for Temperature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_units(f, Units::Imperial)
+    }
+}
+
+impl FromStr for Temperature {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidInput(format!(
+                "{} is not a valid temperature. Try something like 45F or 7C.",
+                s
+            ))
+        };
+        let trimmed = s.trim();
+        let (number, unit) = match trimmed.char_indices().last() {
+            Some((i, unit)) if unit.is_ascii_alphabetic() => (&trimmed[..i], Some(unit)),
+            _ => (trimmed, None),
+        };
+        let number: f32 = number
+            .trim_end_matches('°')
+            .trim()
+            .parse()
+            .map_err(|_| invalid())?;
+        match unit.map(|unit| unit.to_ascii_uppercase()) {
+            None | Some('F') => Ok(Self::from_fahrenheit(number)),
+            Some('C') => Ok(Self::from_celsius(number)),
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+impl From<Temperature> for String {
+    fn from(temperature: Temperature) -> Self {
+        format!("{}°F", temperature.fahrenheit())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(f32),
+    String(String),
+}
+
+impl TryFrom<NumberOrString> for Temperature {
+    type Error = Error;
+
+    fn try_from(value: NumberOrString) -> Result<Self> {
+        match value {
+            NumberOrString::Number(fahrenheit) => Ok(Self::from_fahrenheit(fahrenheit)),
+            NumberOrString::String(s) => s.parse(),
+        }
+    }
+}
+
+/// A speed, independent of any particular unit
+///
+/// Deserializes from either a number, interpreted as mph, or a string with a unit suffix, e.g.
+/// `"12 mph"`, `"20km/h"`, `"5 m/s"`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(try_from = "NumberOrString", into = "String")]
+pub struct Speed {
+    mph: f32,
+}
+
+impl Speed {
+    pub const fn from_mph(mph: f32) -> Self {
+        Self { mph }
+    }
+
+    pub fn from_kph(kph: f32) -> Self {
+        Self::from_mph(kph / 1.609_344)
+    }
+
+    pub fn from_meters_per_second(mps: f32) -> Self {
+        Self::from_kph(mps * 3.6)
+    }
+
+    pub const fn mph(self) -> f32 {
+        self.mph
+    }
+
+    pub fn kph(self) -> f32 {
+        self.mph * 1.609_344
+    }
+}
+
+impl DisplayUnits for Speed {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result {
+        match units {
+            Units::Imperial => write!(f, "{} mph", round(self.mph())),
+            Units::Metric => write!(f, "{} km/h", round(self.kph())),
+        }
+    }
+}
+
+impl fmt::Display for Speed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_units(f, Units::Imperial)
+    }
+}
+
+impl FromStr for Speed {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let trimmed = s.trim();
+        let split = trimmed
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(split);
+        let number: f32 = number.trim().parse().map_err(|_| {
+            Error::InvalidInput(format!(
+                "{} is not a valid speed. Try something like 10mph or 16km/h.",
+                s
+            ))
+        })?;
+        match unit.to_ascii_lowercase().as_str() {
+            "" | "mph" => Ok(Self::from_mph(number)),
+            "kph" | "km/h" | "kmh" => Ok(Self::from_kph(number)),
+            "m/s" | "mps" => Ok(Self::from_meters_per_second(number)),
+            _ => Err(Error::InvalidInput(format!(
+                "{} is not a valid speed. Try something like 10mph or 16km/h.",
+                s
+            ))),
+        }
+    }
+}
+
+impl From<Speed> for String {
+    fn from(speed: Speed) -> Self {
+        // Converted speeds have more digits than anyone needs
+        format!("{} mph", (speed.mph() * 10.0).round() / 10.0)
+    }
+}
+
+impl TryFrom<NumberOrString> for Speed {
+    type Error = Error;
+
+    fn try_from(value: NumberOrString) -> Result<Self> {
+        match value {
+            NumberOrString::Number(mph) => Ok(Self::from_mph(mph)),
+            NumberOrString::String(s) => s.parse(),
+        }
+    }
+}
+
+/// A distance, independent of any particular unit
+///
+/// Parses from a number, interpreted as miles, or a number with a unit suffix, e.g. `"10km"`,
+/// `"10k"`, `"6.2 mi"`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Distance {
+    miles: f32,
+}
+
+impl Distance {
+    pub const fn from_miles(miles: f32) -> Self {
+        Self { miles }
+    }
+
+    pub fn from_kilometers(kilometers: f32) -> Self {
+        Self::from_miles(kilometers / 1.609_344)
+    }
+
+    pub const fn miles(self) -> f32 {
+        self.miles
+    }
+
+    pub fn kilometers(self) -> f32 {
+        self.miles * 1.609_344
+    }
+}
+
+impl FromStr for Distance {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidInput(format!(
+                "{} is not a valid distance. Try something like 6mi or 10km.",
+                s
+            ))
+        };
+        let trimmed = s.trim();
+        let split = trimmed
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(split);
+        let number: f32 = number.trim().parse().map_err(|_| invalid())?;
+        if number <= 0.0 {
+            return Err(invalid());
+        }
+        match unit.to_ascii_lowercase().as_str() {
+            "" | "mi" | "mile" | "miles" => Ok(Self::from_miles(number)),
+            "k" | "km" | "kilometer" | "kilometers" => Ok(Self::from_kilometers(number)),
+            "m" | "meters" => Ok(Self::from_kilometers(number / 1000.0)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// How long it takes to run a unit of distance
+///
+/// Parses from minutes and seconds per mile, e.g. `"8:30"`, or with a unit, e.g. `"8:30/mi"`,
+/// `"5:15/km"`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Pace {
+    seconds_per_mile: f32,
+}
+
+impl Pace {
+    pub const fn from_seconds_per_mile(seconds_per_mile: f32) -> Self {
+        Self { seconds_per_mile }
+    }
+
+    pub fn from_seconds_per_kilometer(seconds_per_kilometer: f32) -> Self {
+        Self::from_seconds_per_mile(seconds_per_kilometer * 1.609_344)
+    }
+
+    pub const fn seconds_per_mile(self) -> f32 {
+        self.seconds_per_mile
+    }
+
+    /// How long it takes to run the given distance, rounded to the minute but at least a minute.
+    /// Returns an error if that's too long to count in minutes.
+    pub fn duration(self, distance: Distance) -> Result<RunDuration> {
+        let minutes = (self.seconds_per_mile * distance.miles() / 60.0).max(1.0);
+        RunDuration::from_fractional_minutes(minutes).ok_or_else(|| {
+            Error::InvalidInput(format!(
+                "{:.1} miles at that pace is longer than a run can last",
+                distance.miles()
+            ))
+        })
+    }
+}
+
+impl FromStr for Pace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidInput(format!(
+                "{} is not a valid pace. Try something like 8:30/mi or 5:15/km.",
+                s
+            ))
+        };
+        let (time, unit) = match s.trim().split_once('/') {
+            Some((time, unit)) => (time, unit.trim()),
+            None => (s, ""),
+        };
+        let (minutes, seconds) = time.trim().split_once(':').unwrap_or((time.trim(), "0"));
+        let minutes: u16 = minutes.parse().map_err(|_| invalid())?;
+        let seconds: u16 = seconds.parse().map_err(|_| invalid())?;
+        if seconds >= 60 || (minutes == 0 && seconds == 0) {
+            return Err(invalid());
+        }
+        let total = minutes
+            .checked_mul(60)
+            .and_then(|total| total.checked_add(seconds))
+            .ok_or_else(invalid)?;
+        let total = f32::from(total);
+        match unit.to_ascii_lowercase().as_str() {
+            "" | "mi" | "mile" => Ok(Self::from_seconds_per_mile(total)),
+            "km" | "kilometer" => Ok(Self::from_seconds_per_kilometer(total)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// How long a run lasts, to the minute
+///
+/// Deserializes from either a number of minutes or a string such as `"45m"`, `"1h30m"`, `"1:30"`,
+/// or an ISO 8601 duration like `"PT45M"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "NumberOrString", into = "String")]
+pub struct RunDuration {
+    minutes: u16,
+}
+
+impl RunDuration {
+    pub const fn from_minutes(minutes: u16) -> Self {
+        Self { minutes }
+    }
+
+    // Round to the minute, or `None` if that's less than a minute or too long to count
+    fn from_fractional_minutes(minutes: f32) -> Option<Self> {
+        let minutes = minutes.round();
+        if (1.0..=f32::from(u16::MAX)).contains(&minutes) {
+            Some(Self::from_minutes(minutes as u16))
+        } else {
+            None
+        }
+    }
+
+    pub const fn minutes(self) -> u16 {
+        self.minutes
+    }
+}
+
+impl fmt::Display for RunDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (hours, minutes) = (self.minutes / 60, self.minutes % 60);
+        match (hours, minutes) {
+            (0, _) => write!(f, "{}m", minutes),
+            (_, 0) => write!(f, "{}h", hours),
+            _ => write!(f, "{}h{}m", hours, minutes),
+        }
+    }
+}
+
+impl FromStr for RunDuration {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidInput(format!(
+                "{} is not a valid duration. Try something like 45m or 1h30m.",
+                s
+            ))
+        };
+        let trimmed = s.trim();
+        if let Some((hours, minutes)) = trimmed.split_once(':') {
+            let hours: u16 = hours.trim().parse().map_err(|_| invalid())?;
+            let minutes: u16 = minutes.trim().parse().map_err(|_| invalid())?;
+            if minutes >= 60 || (hours == 0 && minutes == 0) {
+                return Err(invalid());
+            }
+            let total = hours
+                .checked_mul(60)
+                .and_then(|total| total.checked_add(minutes))
+                .ok_or_else(invalid)?;
+            return Ok(Self::from_minutes(total));
+        }
+        // ISO 8601 durations are just hours and minutes with a prefix
+        let mut rest = match trimmed.get(..2) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("pt") => &trimmed[2..],
+            _ => trimmed,
+        };
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+        // Add up each number and unit, e.g. "1h" and "30m"
+        let mut minutes = 0.0;
+        while !rest.is_empty() {
+            let split = rest
+                .find(|c: char| c.is_ascii_alphabetic())
+                .unwrap_or(rest.len());
+            let (number, after) = rest.split_at(split);
+            let number: f32 = number.trim().parse().map_err(|_| invalid())?;
+            let split = after
+                .find(|c: char| !c.is_ascii_alphabetic())
+                .unwrap_or(after.len());
+            let (unit, after) = after.split_at(split);
+            let scale = match unit.to_ascii_lowercase().as_str() {
+                "" | "m" | "min" | "mins" | "minute" | "minutes" => 1.0,
+                "h" | "hr" | "hrs" | "hour" | "hours" => 60.0,
+                _ => return Err(invalid()),
+            };
+            minutes += number * scale;
+            rest = after.trim_start();
+        }
+        Self::from_fractional_minutes(minutes).ok_or_else(invalid)
+    }
+}
+
+impl From<RunDuration> for String {
+    fn from(duration: RunDuration) -> Self {
+        duration.to_string()
+    }
+}
+
+impl TryFrom<NumberOrString> for RunDuration {
+    type Error = Error;
+
+    fn try_from(value: NumberOrString) -> Result<Self> {
+        match value {
+            NumberOrString::Number(minutes) => {
+                Self::from_fractional_minutes(minutes).ok_or_else(|| {
+                    Error::InvalidInput(format!("{} minutes is not a valid duration", minutes))
+                })
+            }
+            NumberOrString::String(s) => s.parse(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_temperature() {
+        assert_eq!(Temperature::from_fahrenheit(45.0), "45".parse().unwrap());
+        assert_eq!(Temperature::from_fahrenheit(45.0), "45F".parse().unwrap());
+        assert_eq!(Temperature::from_fahrenheit(32.0), "0°C".parse().unwrap());
+        assert_eq!(
+            Temperature::from_fahrenheit(50.0),
+            " 10 c ".parse().unwrap()
+        );
+        assert!("hot".parse::<Temperature>().is_err());
+        assert!("45K".parse::<Temperature>().is_err());
+    }
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!(Speed::from_mph(10.0), "10".parse().unwrap());
+        assert_eq!(Speed::from_mph(10.0), "10 MPH".parse().unwrap());
+        assert_eq!(Speed::from_kph(16.0), "16km/h".parse().unwrap());
+        assert_eq!(Speed::from_meters_per_second(5.0), "5 m/s".parse().unwrap());
+        assert!("fast".parse::<Speed>().is_err());
+        assert!("10 knots".parse::<Speed>().is_err());
+        assert_eq!("4.5 mph", String::from(Speed::from_meters_per_second(2.0)));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        let minutes = |s: &str| s.parse::<RunDuration>().unwrap().minutes();
+        assert_eq!(45, minutes("45"));
+        assert_eq!(45, minutes("45 min"));
+        assert_eq!(90, minutes("1h30m"));
+        assert_eq!(90, minutes("1 hour 30 minutes"));
+        assert_eq!(90, minutes("1.5h"));
+        assert_eq!(90, minutes("1:30"));
+        assert_eq!(90, minutes("PT1H30M"));
+        assert!("".parse::<RunDuration>().is_err());
+        assert!("0m".parse::<RunDuration>().is_err());
+        assert!("1:75".parse::<RunDuration>().is_err());
+        assert!("9999:00".parse::<RunDuration>().is_err());
+        assert!("3 days".parse::<RunDuration>().is_err());
+        assert_eq!("1h30m", RunDuration::from_minutes(90).to_string());
+        assert_eq!("45m", RunDuration::from_minutes(45).to_string());
+
+        // A 10K at 8:03/mi, which is 5:00/km
+        let distance: Distance = "10km".parse().unwrap();
+        assert_eq!(
+            50,
+            "5:00/km"
+                .parse::<Pace>()
+                .unwrap()
+                .duration(distance)
+                .unwrap()
+                .minutes()
+        );
+        assert_eq!(
+            50,
+            "8:03"
+                .parse::<Pace>()
+                .unwrap()
+                .duration(distance)
+                .unwrap()
+                .minutes()
+        );
+        // A sprint still lasts a minute
+        assert_eq!(
+            1,
+            "8:00"
+                .parse::<Pace>()
+                .unwrap()
+                .duration("100m".parse().unwrap())
+                .unwrap()
+                .minutes()
+        );
+        assert!("30:00"
+            .parse::<Pace>()
+            .unwrap()
+            .duration("3000mi".parse().unwrap())
+            .is_err());
+        assert!(serde_json::from_str::<RunDuration>("1e9").is_err());
+        assert_eq!(
+            RunDuration::from_minutes(45),
+            serde_json::from_str("45").unwrap()
+        );
+        assert!("6 furlongs".parse::<Distance>().is_err());
+        assert!("8:30/hr".parse::<Pace>().is_err());
+        assert!("9999:00".parse::<Pace>().is_err());
+    }
+
+    #[test]
+    fn test_display_units() {
+        let temperature = Temperature::from_fahrenheit(45.0);
+        assert_eq!("45°F", temperature.in_units(Units::Imperial).to_string());
+        assert_eq!("7°C", temperature.in_units(Units::Metric).to_string());
+        assert_eq!(
+            "0°C",
+            Temperature::from_celsius(-0.2)
+                .in_units(Units::Metric)
+                .to_string()
+        );
+        let speed = Speed::from_meters_per_second(5.0);
+        assert_eq!("11 mph", speed.to_string());
+        assert_eq!("18 km/h", speed.in_units(Units::Metric).to_string());
+    }
+}
@@ -1,16 +1,30 @@
+use crate::humidity::{self, Comfort};
+use crate::inputs::parse_choice;
+use crate::sun::{self, Daylight, SunPosition, UvCategory};
+use crate::units::{DisplayUnits, Speed, Temperature, Units};
 use crate::{Error, Result};
-use log::debug;
-use openweather::{Language, LocationSpecifier, Settings, Unit, WeatherReportCurrent};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde_derive::{Deserialize, Serialize};
 use std::fmt;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Weather {
     Clear,
     PartlyCloudy,
     Overcast,
+    /// Fog or mist
+    Fog,
+    /// Haze, smoke, dust, or anything else hanging in the air that isn't water
+    Haze,
+    Drizzle,
     Rain,
     HeavyRain,
+    Thunderstorm,
+    FreezingRain,
+    /// Ice pellets, or a mix of rain and snow
+    Sleet,
     Snow,
 }
 
@@ -20,7 +34,67 @@ impl Default for Weather {
     }
 }
 
-#[derive(Debug, Clone)]
+impl fmt::Display for Weather {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Clear => "clear",
+            Self::PartlyCloudy => "partly cloudy",
+            Self::Overcast => "overcast",
+            Self::Fog => "fog",
+            Self::Haze => "haze",
+            Self::Drizzle => "drizzle",
+            Self::Rain => "rain",
+            Self::HeavyRain => "heavy rain",
+            Self::Thunderstorm => "thunderstorms",
+            Self::FreezingRain => "freezing rain",
+            Self::Sleet => "sleet",
+            Self::Snow => "snow",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Weather {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_choice(
+            "weather",
+            s,
+            &[
+                ("clear", Self::Clear),
+                ("partly-cloudy", Self::PartlyCloudy),
+                ("overcast", Self::Overcast),
+                ("fog", Self::Fog),
+                ("haze", Self::Haze),
+                ("drizzle", Self::Drizzle),
+                ("rain", Self::Rain),
+                ("heavy-rain", Self::HeavyRain),
+                ("thunderstorm", Self::Thunderstorm),
+                ("freezing-rain", Self::FreezingRain),
+                ("sleet", Self::Sleet),
+                ("snow", Self::Snow),
+            ],
+        )
+    }
+}
+
+impl Weather {
+    /// Rough fraction of UV that gets through compared to a clear sky
+    pub const fn uv_transmission(self) -> f32 {
+        match self {
+            Self::Clear => 1.0,
+            // Haze and broken clouds block surprisingly little
+            Self::PartlyCloudy | Self::Haze => 0.8,
+            Self::Fog | Self::Drizzle => 0.5,
+            Self::Overcast | Self::Rain | Self::Snow | Self::Sleet | Self::FreezingRain => 0.3,
+            Self::HeavyRain | Self::Thunderstorm => 0.2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Wind {
     Calm,
     Light,
@@ -33,7 +107,70 @@ impl Default for Wind {
     }
 }
 
-#[derive(Debug, Clone)]
+impl Wind {
+    pub fn from_speed(speed: Speed) -> Self {
+        let mph = speed.mph();
+        if mph < 8.0 {
+            Self::Calm
+        } else if mph < 17.0 {
+            Self::Light
+        } else {
+            Self::Heavy
+        }
+    }
+
+    /// A typical speed for this kind of wind, for when the actual speed isn't known
+    pub const fn typical_speed(self) -> Speed {
+        match self {
+            Self::Calm => Speed::from_mph(0.0),
+            Self::Light => Speed::from_mph(10.0),
+            Self::Heavy => Speed::from_mph(20.0),
+        }
+    }
+}
+
+/// How cold it feels in the wind, using the
+/// [NWS wind chill formula](https://www.weather.gov/media/epz/wxcalc/windChill.pdf)
+///
+/// The formula is only meant for temperatures at or below 50°F. Winds under 3 mph have no effect.
+pub fn wind_chill(temperature: Temperature, speed: Speed) -> Temperature {
+    let (t, v) = (temperature.fahrenheit(), speed.mph());
+    if v < 3.0 {
+        return temperature;
+    }
+    let v = v.powf(0.16);
+    Temperature::from_fahrenheit(35.74 + 0.6215 * t - 35.75 * v + 0.4275 * t * v)
+}
+
+impl fmt::Display for Wind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Calm => "calm",
+            Self::Light => "light",
+            Self::Heavy => "heavy",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Wind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_choice(
+            "wind",
+            s,
+            &[
+                ("calm", Self::Calm),
+                ("light", Self::Light),
+                ("heavy", Self::Heavy),
+            ],
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TimeOfDay {
     Morning,
     Daytime,
@@ -47,120 +184,480 @@ impl Default for TimeOfDay {
     }
 }
 
-#[derive(Default, Debug, Clone)]
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Morning => "morning",
+            Self::Daytime => "daytime",
+            Self::Evening => "evening",
+            Self::Night => "night",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for TimeOfDay {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_choice(
+            "time of day",
+            s,
+            &[
+                ("morning", Self::Morning),
+                ("daytime", Self::Daytime),
+                ("evening", Self::Evening),
+                ("night", Self::Night),
+            ],
+        )
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Conditions {
-    // Temperature in units (°F)
-    pub temperature: i16,
+    pub temperature: Temperature,
     pub weather: Weather,
+    /// Rough strength of the wind, for display and rules. Derived from `wind_speed` if known.
     pub wind: Wind,
     pub time: TimeOfDay,
+    /// Angle of the sun above the horizon in degrees, if the location is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sun_elevation: Option<f32>,
+    /// How warm it feels according to the weather provider, if it says. For reference only, since
+    /// outfits are picked using the effective temperature instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apparent_temperature: Option<Temperature>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uv_index: Option<f32>,
+    /// Sustained wind speed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wind_speed: Option<Speed>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wind_gust: Option<Speed>,
+    /// Relative humidity in percent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub humidity: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dew_point: Option<Temperature>,
+    /// When the provider observed these conditions, or issued the forecast they came from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observed_at: Option<DateTime<Utc>>,
+}
+
+impl Conditions {
+    /// Sets the time of day and sun elevation from where the sun is
+    pub fn set_sun_position(&mut self, sun: SunPosition) {
+        self.time = sun.time_of_day();
+        self.sun_elevation = Some(sun.elevation);
+    }
+
+    /// How light it is, from the sun's position if known or else the time of day
+    pub fn daylight(&self) -> Daylight {
+        match (self.sun_elevation, self.time) {
+            (Some(elevation), _) => Daylight::from_elevation(elevation),
+            (None, TimeOfDay::Night) => Daylight::Dark,
+            (None, TimeOfDay::Morning) | (None, TimeOfDay::Evening) => Daylight::Twilight,
+            (None, TimeOfDay::Daytime) => Daylight::Day,
+        }
+    }
+
+    /// UV index from the provider, or else estimated from the sun and clouds
+    pub fn estimated_uv_index(&self) -> f32 {
+        self.uv_index.unwrap_or_else(|| {
+            // Without the sun's position, assume a typical one for the time of day
+            let elevation = self.sun_elevation.unwrap_or(match self.time {
+                TimeOfDay::Daytime => 45.0,
+                TimeOfDay::Morning | TimeOfDay::Evening => 10.0,
+                TimeOfDay::Night => 0.0,
+            });
+            sun::clear_sky_uv_index(elevation) * self.weather.uv_transmission()
+        })
+    }
+
+    pub fn uv_category(&self) -> UvCategory {
+        UvCategory::from_index(self.estimated_uv_index())
+    }
+
+    /// Sustained wind speed from the provider, or else a typical speed for the kind of wind
+    pub fn estimated_wind_speed(&self) -> Speed {
+        self.wind_speed.unwrap_or_else(|| self.wind.typical_speed())
+    }
+
+    /// Relative humidity from the provider, or else estimated from the dew point
+    pub fn relative_humidity(&self) -> Option<f32> {
+        self.humidity.or_else(|| {
+            self.dew_point
+                .map(|dew_point| humidity::relative_humidity(self.temperature, dew_point))
+        })
+    }
+
+    /// Dew point from the provider, or else estimated from the humidity
+    pub fn estimated_dew_point(&self) -> Option<Temperature> {
+        self.dew_point.or_else(|| {
+            self.humidity
+                .map(|humidity| humidity::dew_point(self.temperature, humidity))
+        })
+    }
+
+    /// How humid it feels, if the humidity or dew point is known
+    pub fn comfort(&self) -> Option<Comfort> {
+        self.estimated_dew_point().map(Comfort::from_dew_point)
+    }
 }
 
 impl Conditions {
-    fn validate(&self) -> Result<()> {
-        match self.weather {
-            Weather::Rain | Weather::HeavyRain => {
-                if self.temperature < 30 {
-                    return Err(Error::InvalidWeather(self.clone()));
-                }
+    /// Check for combinations of temperature and weather that can't happen, e.g. rain below 30°F
+    pub fn validate(&self) -> Result<()> {
+        let reason = match self.weather {
+            Weather::Drizzle | Weather::Rain | Weather::HeavyRain
+                if self.temperature.fahrenheit() < 30.0 =>
+            {
+                format!(
+                    "{} isn't possible below {}",
+                    self.weather,
+                    Temperature::from_fahrenheit(30.0)
+                )
             }
-            Weather::Snow => {
-                if self.temperature > 45 {
-                    return Err(Error::InvalidWeather(self.clone()));
-                }
+            Weather::FreezingRain | Weather::Sleet | Weather::Snow
+                if self.temperature.fahrenheit() > 45.0 =>
+            {
+                format!(
+                    "{} isn't possible above {}",
+                    self.weather,
+                    Temperature::from_fahrenheit(45.0)
+                )
             }
-            _ => (),
+            _ => return Ok(()),
         };
-        Ok(())
+        Err(Error::InvalidWeather {
+            conditions: self.clone(),
+            reason,
+        })
     }
 }
 
 // TODO: pretty print
+impl DisplayUnits for Conditions {
+    fn fmt_units(&self, f: &mut fmt::Formatter, units: Units) -> fmt::Result {
+        write!(f, "{}", self.temperature.in_units(units))?;
+        if let Some(apparent_temperature) = self.apparent_temperature {
+            // Labeled so it isn't mistaken for the effective temperature, which the outfit uses
+            write!(
+                f,
+                " (provider feels-like {})",
+                apparent_temperature.in_units(units)
+            )?;
+        }
+        write!(f, " @ {:?}", self.time)?;
+        if let Some(sun_elevation) = self.sun_elevation.filter(|&elevation| elevation > 0.0) {
+            write!(f, ", sun {:.0}° up", sun_elevation)?;
+        }
+        write!(f, "\n{:?} with {:?} wind", self.weather, self.wind)?;
+        if let Some(wind_speed) = self.wind_speed {
+            write!(f, " at {}", wind_speed.in_units(units))?;
+        }
+        if let Some(wind_gust) = self.wind_gust {
+            write!(f, ", gusting to {}", wind_gust.in_units(units))?;
+        }
+        if let Some(humidity) = self.relative_humidity() {
+            write!(f, ", {:.0}% humidity", humidity)?;
+        }
+        let uv_index = self.estimated_uv_index();
+        if self.uv_index.is_some() {
+            write!(f, ", UV index {:.0} ({})", uv_index, self.uv_category())?;
+        } else if uv_index.round() >= 1.0 {
+            write!(
+                f,
+                ", UV index about {:.0} ({})",
+                uv_index,
+                self.uv_category()
+            )?;
+        }
+        if let (Some(dew_point), Some(comfort)) = (self.estimated_dew_point(), self.comfort()) {
+            write!(
+                f,
+                "\nDew point {}, feels {}",
+                dew_point.in_units(units),
+                comfort
+            )?;
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Display for Conditions {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}°F @ {:?}\n{:?} with {:?} wind",
-            self.temperature, self.time, self.weather, self.wind
-        )
+        self.fmt_units(f, Units::default())
     }
 }
 
-fn resolve_wind(weather: &WeatherReportCurrent) -> Wind {
-    let wind_speed = weather.wind.speed;
-    if wind_speed < 8.0 {
-        Wind::Calm
-    } else if wind_speed < 17.0 {
-        Wind::Light
-    } else {
-        Wind::Heavy
+/// How old weather can be and still be used to pick an outfit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Freshness {
+    /// Oldest weather to use
+    pub max_age: Duration,
+    /// How far in the future weather can claim to be from and still be treated as current, to allow
+    /// for the provider's clock being a little ahead
+    pub max_skew: Duration,
+}
+
+impl Default for Freshness {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::hours(3),
+            max_skew: Duration::minutes(5),
+        }
     }
 }
 
-fn resolve_time_of_day(weather: &WeatherReportCurrent) -> TimeOfDay {
-    // Rough heuristic that ignores higher latitudes
-    // Morning lasts from an hour before sunrise to two hours after sunrise
-    if weather.dt + 3600 > weather.sys.sunrise && weather.dt < weather.sys.sunrise + 2 * 3600 {
-        return TimeOfDay::Morning;
+impl Freshness {
+    /// Check that weather observed or fetched at `observed_at` is recent enough to use as of `now`,
+    /// returning how old it is. Weather with no time is assumed to be current.
+    pub fn check(
+        &self,
+        observed_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Duration>> {
+        let age = match observed_at {
+            Some(observed_at) => now - observed_at,
+            None => return Ok(None),
+        };
+        if age > self.max_age {
+            return Err(Error::StaleWeather { age });
+        }
+        if age < -self.max_skew {
+            return Err(Error::InvalidInput(format!(
+                "The weather is from {} in the future. Is the clock set correctly?",
+                describe_duration(-age)
+            )));
+        }
+        Ok(Some(age.max(Duration::zero())))
     }
-    // Daytime is from sunrise + 2H to sunset - 1H
-    if weather.dt >= weather.sys.sunrise + 2 * 3600 && weather.dt + 3600 < weather.sys.sunset {
-        return TimeOfDay::Daytime;
+}
+
+// Describe a duration in rough terms, e.g. "25 minutes" or "3 hours"
+fn describe_duration(duration: Duration) -> String {
+    match duration.num_minutes() {
+        1 => "1 minute".to_string(),
+        minutes @ 0..=119 => format!("{} minutes", minutes),
+        minutes @ 120..=2879 => format!("{} hours", minutes / 60),
+        minutes => format!("{} days", minutes / (24 * 60)),
     }
-    // Evening is within an hour of sunset
-    if weather.dt + 3600 >= weather.sys.sunset && weather.dt < weather.sys.sunset + 3600 {
-        return TimeOfDay::Evening;
+}
+
+/// Describe how long ago weather of the given age is from, e.g. "40 minutes ago"
+pub fn describe_age(age: Duration) -> String {
+    if age < Duration::minutes(1) {
+        "just now".to_string()
+    } else {
+        format!("{} ago", describe_duration(age))
     }
+}
 
-    TimeOfDay::Night
+/// Conditions forecast for a particular time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForecastEntry {
+    pub time: DateTime<Utc>,
+    pub conditions: Conditions,
 }
 
-fn resolve_weather(weather: &WeatherReportCurrent) -> Weather {
-    // TODO: replace magic numbers
+/// Upcoming conditions at a location, in steps of up to 3 hours
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Forecast {
+    /// Offset of the location's local time from UTC
+    #[serde(with = "utc_offset_seconds")]
+    pub utc_offset: FixedOffset,
+    pub entries: Vec<ForecastEntry>,
+    /// When the forecast was fetched from the provider, if it came from one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued_at: Option<DateTime<Utc>>,
+}
 
-    // Check for precipitation
-    for weather_item in &weather.weather {
-        match weather_item.id {
-            600..=699 => return Weather::Snow,
-            500 | 501 | 520 => return Weather::Rain,
-            #[allow(overlapping_patterns)]
-            500..=599 => return Weather::HeavyRain,
-            _ => (),
-        }
+// (De)serialize a UTC offset as a number of seconds east of UTC
+mod utc_offset_seconds {
+    use chrono::FixedOffset;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        offset: &FixedOffset,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(offset.local_minus_utc())
     }
-    // No precipitation. Can use % cloud cover to determine weather
-    if weather.clouds.all > 75 {
-        Weather::Overcast
-    } else if weather.clouds.all > 25 {
-        Weather::PartlyCloudy
-    } else {
-        Weather::Clear
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<FixedOffset, D::Error> {
+        let seconds = i32::deserialize(deserializer)?;
+        FixedOffset::east_opt(seconds)
+            .ok_or_else(|| de::Error::custom(format!("invalid UTC offset: {}", seconds)))
+    }
+}
+
+impl Forecast {
+    // Longest time between forecast entries
+    const STEP_SEC: i64 = 3 * 3600;
+
+    /// Conditions forecast for the entry closest to the given time
+    pub fn at(&self, time: DateTime<Utc>) -> Result<Conditions> {
+        let distance = |entry: &ForecastEntry| (entry.time - time).num_seconds().abs();
+        self.entries
+            .iter()
+            .min_by_key(|entry| distance(entry))
+            .filter(|entry| distance(entry) <= Self::STEP_SEC / 2)
+            .map(|entry| Conditions {
+                observed_at: entry.conditions.observed_at.or(self.issued_at),
+                ..entry.conditions.clone()
+            })
+            .ok_or_else(|| {
+                Error::InvalidInput(format!(
+                    "There's no forecast for {}. Forecasts only cover the next few days.",
+                    time.with_timezone(&self.utc_offset)
+                        .format("%a %b %-d %-I:%M %p")
+                ))
+            })
+    }
+
+    /// Convert a time in the location's local time to UTC
+    pub fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let offset = Duration::seconds(self.utc_offset.local_minus_utc().into());
+        Utc.from_utc_datetime(&(local - offset))
+    }
+
+    /// Current time in the location's local time
+    pub fn now_local(&self) -> NaiveDateTime {
+        Utc::now().with_timezone(&self.utc_offset).naive_local()
     }
 }
 
-pub fn get_current(owm_api_key: &str, loc: &LocationSpecifier) -> Result<Conditions> {
-    debug!("Getting weather for {:?}", loc);
+/// The next time matching the given local date and/or time of day, e.g. the next 6am if no date
+/// is given. Runs are assumed to be in the morning if no time is given.
+///
+/// Returns an error if a date is given and the time on that date has already passed.
+pub fn next_occurrence(
+    now: NaiveDateTime,
+    date: Option<NaiveDate>,
+    time: Option<NaiveTime>,
+) -> Result<NaiveDateTime> {
+    let time = time.unwrap_or_else(|| NaiveTime::from_hms_opt(7, 0, 0).unwrap());
+    match date {
+        Some(date) if date.and_time(time) < now => Err(Error::InvalidInput(format!(
+            "{} has already passed. Try a later time.",
+            date.and_time(time).format("%a %b %-d at %-I:%M %p")
+        ))),
+        Some(date) => Ok(date.and_time(time)),
+        None if now.time() <= time => Ok(now.date().and_time(time)),
+        None => Ok((now.date() + Duration::days(1)).and_time(time)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let settings: Settings = Settings {
-        unit: Some(Unit::Imperial),
-        lang: Some(Language::English),
-    };
+    #[test]
+    fn test_validate() {
+        let conditions = Conditions {
+            temperature: "25F".parse().unwrap(),
+            weather: "heavy rain".parse().unwrap(),
+            wind: "calm".parse().unwrap(),
+            time: "Night".parse().unwrap(),
+            ..Conditions::default()
+        };
+        assert_eq!(
+            "The given weather conditions are invalid: heavy rain isn't possible below 30°F",
+            conditions.validate().unwrap_err().to_string()
+        );
+        assert!(Conditions {
+            weather: Weather::Snow,
+            ..conditions
+        }
+        .validate()
+        .is_ok());
+        assert!(Conditions {
+            weather: Weather::FreezingRain,
+            ..conditions
+        }
+        .validate()
+        .is_ok());
+        assert!("sunny".parse::<Weather>().is_err());
+        assert_eq!(Weather::FreezingRain, "Freezing Rain".parse().unwrap());
+    }
 
-    let weather = openweather::get_current_weather(loc, owm_api_key, &settings)?;
-    let forecast_time = UNIX_EPOCH + Duration::from_secs(weather.dt);
-    let freshness_sec = SystemTime::now().duration_since(forecast_time)?.as_secs();
+    #[test]
+    fn test_wind_chill() {
+        // Values from the NWS wind chill chart
+        let chill = |fahrenheit, mph| {
+            wind_chill(
+                Temperature::from_fahrenheit(fahrenheit),
+                Speed::from_mph(mph),
+            )
+            .fahrenheit()
+            .round()
+        };
+        assert_eq!(25.0, chill(30.0, 5.0));
+        assert_eq!(-9.0, chill(10.0, 20.0));
+        assert_eq!(40.0, chill(40.0, 2.0));
+    }
 
-    debug!(
-        "Fetched OpenWeatherMap data from {} minutes ago",
-        freshness_sec / 60
-    );
+    #[test]
+    fn test_freshness() {
+        let now = Utc.with_ymd_and_hms(2020, 10, 18, 12, 0, 0).unwrap();
+        let freshness = Freshness::default();
+        assert_eq!(None, freshness.check(None, now).unwrap());
+        assert_eq!(
+            Some(Duration::minutes(40)),
+            freshness
+                .check(Some(now - Duration::minutes(40)), now)
+                .unwrap()
+        );
+        assert_eq!(
+            Some(Duration::zero()),
+            freshness
+                .check(Some(now + Duration::minutes(2)), now)
+                .unwrap()
+        );
+        assert!(matches!(
+            freshness.check(Some(now + Duration::hours(1)), now),
+            Err(Error::InvalidInput(_))
+        ));
+        let err = freshness
+            .check(Some(now - Duration::hours(5)), now)
+            .unwrap_err();
+        assert_eq!(
+            "The latest weather is from 5 hours ago, which is too old to use",
+            err.to_string()
+        );
+        assert_eq!("40 minutes ago", describe_age(Duration::minutes(40)));
+        assert_eq!("just now", describe_age(Duration::seconds(30)));
+    }
 
-    let conditions: Conditions = Conditions {
-        temperature: weather.main.temp.round() as i16,
-        wind: resolve_wind(&weather),
-        time: resolve_time_of_day(&weather),
-        weather: resolve_weather(&weather),
-    };
-    conditions.validate()?;
-    Ok(conditions)
+    #[test]
+    fn test_next_occurrence() {
+        let now = NaiveDate::from_ymd_opt(2020, 10, 18)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap();
+        let six_am = NaiveTime::from_hms_opt(6, 0, 0).unwrap();
+        let tomorrow = NaiveDate::from_ymd_opt(2020, 10, 19).unwrap();
+        assert_eq!(
+            tomorrow.and_time(six_am),
+            next_occurrence(now, None, Some(six_am)).unwrap()
+        );
+        assert_eq!(
+            now.date().and_hms_opt(18, 0, 0).unwrap(),
+            next_occurrence(now, None, Some(NaiveTime::from_hms_opt(18, 0, 0).unwrap())).unwrap()
+        );
+        assert_eq!(
+            tomorrow.and_hms_opt(7, 0, 0).unwrap(),
+            next_occurrence(now, Some(tomorrow), None).unwrap()
+        );
+        assert_eq!(
+            "Sun Oct 18 at 6:00 AM has already passed. Try a later time.",
+            next_occurrence(now, Some(now.date()), Some(six_am))
+                .unwrap_err()
+                .to_string()
+        );
+    }
 }
@@ -0,0 +1,41 @@
+use crate::error::OutfitHandlerError;
+use alexa_sdk::Request;
+use reqwest::blocking::Response;
+
+type Result<T> = std::result::Result<T, OutfitHandlerError>;
+
+// Make an authenticated GET request to the Alexa device API. `path` may contain "{device_id}",
+// which is replaced with the ID of the requesting device.
+pub fn get(req: &Request, path: &str) -> Result<Response> {
+    let api_endpoint = req
+        .context
+        .system
+        .api_endpoint
+        .as_ref()
+        .ok_or_else(|| OutfitHandlerError::InvalidAlexaRequest(Box::new(req.clone())))?;
+    let auth_header = req
+        .context
+        .system
+        .api_access_token
+        .as_ref()
+        .map(|token| format!("Bearer {}", token))
+        .ok_or_else(|| OutfitHandlerError::InvalidAlexaRequest(Box::new(req.clone())))?;
+    let device_id = &req
+        .context
+        .system
+        .device
+        .as_ref()
+        .ok_or_else(|| OutfitHandlerError::InvalidAlexaRequest(Box::new(req.clone())))?
+        .device_id;
+    let uri = format!(
+        "{api_endpoint}{path}",
+        api_endpoint = api_endpoint,
+        path = path.replace("{device_id}", device_id)
+    );
+    let client = reqwest::blocking::Client::new();
+    Ok(client
+        .get(&uri)
+        .header("Accept", "application/json")
+        .header("Authorization", auth_header)
+        .send()?)
+}
@@ -1,6 +1,9 @@
+mod device_api;
 mod error;
 mod location;
 mod outfit_intent;
+mod settings;
+mod when;
 
 use alexa_sdk::request::IntentType;
 use alexa_sdk::{Request, Response};
@@ -10,7 +13,9 @@ use log::{error, warn};
 fn handle_help(_req: &Request) -> Result<Response> {
     Ok(Response::simple(
         "Help 👟",
-        "Outfit Picker can help you pick a running outfit. Try saying \"find me an outfit\".",
+        "Outfit Picker can help you pick a running outfit. Try saying \"find me an outfit\" or \"what \
+        should I wear tomorrow morning?\". You can also ask \"why no gloves?\" to find out why \
+        something was or wasn't picked, or \"when should I run tomorrow?\"",
     ))
 }
 
@@ -31,6 +36,8 @@ async fn my_handler(req: Request) -> Result<Response> {
         IntentType::Cancel | IntentType::Stop => handle_cancel(&req),
         IntentType::Fallback => handle_fallback(&req),
         IntentType::Help => handle_help(&req),
+        IntentType::User(name) if name == "WhyOutfit" => outfit_intent::why_handler(&req),
+        IntentType::User(name) if name == "BestTime" => outfit_intent::best_time_handler(&req),
         IntentType::User(_) => outfit_intent::handler(&req),
         intent => {
             warn!("Unknown intent {:?}", intent);
@@ -1,16 +1,25 @@
 use crate::error::OutfitHandlerError;
 use crate::location;
+use crate::settings;
+use crate::when;
 use alexa_sdk::{
     request::IntentType,
     response::{Card, Speech},
     Request, Response,
 };
-use anyhow::anyhow;
+use chrono::{DateTime, Duration, Utc};
 use log::{error, info, warn};
 use picker::{
-    gear::Outfit,
-    inputs::{Intensity, RunParameters, Sex, UserPreferences},
-    weather,
+    gear::{Decision, Explanation, GearCatalog, Outfit, Verdict},
+    humidity::Comfort,
+    inputs::{EffectiveTemperature, Intensity, RunParameters, Sex, UserPreferences},
+    planner::Plan,
+    providers::{CacheSettings, Location, ProviderKind, ProviderSettings, WeatherProvider},
+    safety,
+    sun::UvCategory,
+    trend::Trend,
+    units::{DisplayUnits, RunDuration, Units},
+    weather::{self, Conditions, Freshness},
 };
 use std::fmt::Write;
 
@@ -20,6 +29,10 @@ const INSUFFICIENT_LOCATION_PERMISSION_TEXT: &str =
 const FETCH_WEATHER_ERROR_TEXT: &str =
     "I had an issue retrieving weather data for your location. Please try again later.";
 const FETCH_WEATHER_ERROR_TITLE: &str = "No weather data";
+const NO_FORECAST_TITLE: &str = "No forecast";
+const STALE_WEATHER_TITLE: &str = "Old weather data";
+// Only mention how old the weather is when it's at least this many minutes old
+const MENTION_AGE_MINUTES: i64 = 30;
 
 // Flatten a collection of strings into a single string, adding in commas as necessary, and adding
 // "and" before the last item.
@@ -32,6 +45,24 @@ fn join_english_list(items: &[&str]) -> String {
     }
 }
 
+fn as_strs(items: &[String]) -> Vec<&str> {
+    items.iter().map(String::as_str).collect()
+}
+
+fn temperature_to_speech(temperature: &EffectiveTemperature, units: Units) -> String {
+    let reasons = temperature.weather_reasons();
+    if reasons.is_empty() {
+        format!("It's {} degrees.", temperature.base.round(units))
+    } else {
+        format!(
+            "It's {} degrees, but it feels like {} because of the {}.",
+            temperature.base.round(units),
+            temperature.feels_like().round(units),
+            join_english_list(&reasons)
+        )
+    }
+}
+
 fn outfit_to_speech(outfit: &Outfit) -> Result<String, OutfitHandlerError> {
     let mut speech = String::new();
     if !outfit.torso.is_empty() || !outfit.legs.is_empty() {
@@ -44,7 +75,7 @@ fn outfit_to_speech(outfit: &Outfit) -> Result<String, OutfitHandlerError> {
                     .torso
                     .iter()
                     .chain(outfit.legs.iter())
-                    .copied()
+                    .map(String::as_str)
                     .collect::<Vec<&str>>()
             )
         )?;
@@ -52,21 +83,107 @@ fn outfit_to_speech(outfit: &Outfit) -> Result<String, OutfitHandlerError> {
     }
     if !outfit.head.is_empty() {
         write!(&mut speech, "On your head, you should wear ")?;
-        write!(&mut speech, "{}", join_english_list(&outfit.head))?;
+        write!(&mut speech, "{}", join_english_list(&as_strs(&outfit.head)))?;
         write!(&mut speech, ". ")?;
     }
     if !outfit.accessories.is_empty() {
         write!(&mut speech, "Don't forget ")?;
-        write!(&mut speech, "{}", join_english_list(&outfit.accessories))?;
+        write!(
+            &mut speech,
+            "{}",
+            join_english_list(&as_strs(&outfit.accessories))
+        )?;
         write!(&mut speech, "!")?;
     }
     Ok(speech.trim().to_string())
 }
 
-pub fn handler(req: &Request) -> anyhow::Result<Response> {
-    let owm_api_key =
-        std::env::var("OWM_API_KEY").map_err(|_| anyhow!("No OpenWeatherMap API key provided"))?;
+fn decision_to_speech(decision: &Decision, units: Units) -> String {
+    let name = &decision.name;
+    match &decision.verdict {
+        Verdict::Picked => format!("I picked {} because it suits the conditions", name),
+        Verdict::TooWarm { temperature, .. } => format!(
+            "I skipped {} because it feels like {} degrees, which is too warm for it",
+            name,
+            temperature.round(units)
+        ),
+        Verdict::TooCold { temperature, .. } => format!(
+            "I skipped {} because it feels like {} degrees, which is too cold for it",
+            name,
+            temperature.round(units)
+        ),
+        Verdict::RuleFailed(rule) => {
+            format!(
+                "I skipped {} because it's only for when {}",
+                name,
+                rule.in_units(units)
+            )
+        }
+        Verdict::RaceTop => format!("I picked {} because it's race day", name),
+        Verdict::ReplacedByRaceTop(race_top) => format!(
+            "I skipped {} because {} is a better choice on race day",
+            name, race_top
+        ),
+    }
+}
 
+// Explain the decision for the gear the user asked about, or summarize the outfit if they didn't
+// ask about anything specific
+fn explanation_to_speech(explanation: &Explanation, query: Option<&str>, units: Units) -> String {
+    match query {
+        Some(query) => {
+            let decisions = explanation.find(query);
+            if decisions.is_empty() {
+                format!("Sorry, I don't know anything about {}.", query)
+            } else {
+                let reasons: Vec<String> = decisions
+                    .into_iter()
+                    .map(|decision| decision_to_speech(decision, units))
+                    .collect();
+                format!("{}.", reasons.join(". "))
+            }
+        }
+        None => {
+            let picked: Vec<&str> = explanation
+                .decisions
+                .iter()
+                .filter(|decision| decision.verdict.is_picked())
+                .map(|decision| decision.name.as_str())
+                .collect();
+            format!(
+                "It feels like {} degrees, so I picked {}.",
+                explanation.effective_temperature.round(units),
+                join_english_list(&picked)
+            )
+        }
+    }
+}
+
+// Say what's missing when the catalog can't cover the basics
+fn incomplete_outfit_to_speech(explanation: &Explanation, units: Units) -> String {
+    let missing: Vec<String> = explanation
+        .missing()
+        .iter()
+        .map(|slot| format!("your {:?}", slot).to_lowercase())
+        .collect();
+    let missing: Vec<&str> = missing.iter().map(String::as_str).collect();
+    format!(
+        "It feels like {} degrees, and I don't have anything for {} at that temperature.",
+        explanation.effective_temperature.round(units),
+        join_english_list(&missing)
+    )
+}
+
+fn get_slot_value<'a>(req: &'a Request, name: &str) -> Option<&'a str> {
+    req.body
+        .intent
+        .as_ref()
+        .and_then(|intent| intent.slots.as_ref())
+        .and_then(|slots| slots.get(name))
+        .and_then(|slot| slot.value.as_deref())
+}
+
+fn get_preferences(req: &Request) -> UserPreferences {
     let sex = req
         .body
         .intent
@@ -99,25 +216,119 @@ pub fn handler(req: &Request) -> anyhow::Result<Response> {
         _ => Intensity::Average,
     };
 
-    let preferences = UserPreferences {
+    // AMAZON.DURATION slots are ISO 8601 durations, e.g. "PT45M"
+    let duration = get_slot_value(req, "duration").and_then(|value| match value.parse() {
+        Ok(duration) => Some(duration),
+        Err(e) => {
+            warn!("Ignoring invalid duration: {}", e);
+            None
+        }
+    });
+
+    UserPreferences {
         sex,
         intensity,
+        units: settings::get_units(req),
+        duration,
         ..UserPreferences::default()
+    }
+}
+
+// Pick the weather provider from the WEATHER_PROVIDER environment variable, defaulting to
+// OpenWeatherMap
+fn get_provider() -> anyhow::Result<Box<dyn WeatherProvider>> {
+    let kind = match std::env::var("WEATHER_PROVIDER") {
+        Ok(name) => name.parse()?,
+        Err(_) => ProviderKind::default(),
+    };
+    // The lambda's scratch space is kept between requests while it's warm
+    let mut cache = CacheSettings::new(std::env::temp_dir().join("wtwr-cache"));
+    if let Ok(minutes) = std::env::var("WEATHER_CACHE_MINUTES") {
+        cache.ttl = Duration::minutes(minutes.parse()?);
+    }
+    let settings = ProviderSettings {
+        owm_api_key: std::env::var("OWM_API_KEY").ok(),
+        fixture: std::env::var_os("WEATHER_FIXTURE").map(Into::into),
+        cache: Some(cache),
     };
+    Ok(kind.build(&settings)?)
+}
+
+// Check that weather from `observed_at` isn't too old to use, returning how old it is. The oldest
+// weather to use can be set in minutes with the WEATHER_MAX_AGE_MINUTES environment variable.
+fn check_freshness(
+    observed_at: Option<DateTime<Utc>>,
+) -> Result<Option<Duration>, OutfitHandlerError> {
+    let mut freshness = Freshness::default();
+    if let Ok(minutes) = std::env::var("WEATHER_MAX_AGE_MINUTES") {
+        match minutes.parse() {
+            Ok(minutes) => freshness.max_age = Duration::minutes(minutes),
+            Err(_) => warn!("Ignoring invalid WEATHER_MAX_AGE_MINUTES: {}", minutes),
+        }
+    }
+    Ok(freshness.check(observed_at, Utc::now())?)
+}
+
+// Get the current conditions, or the forecast if the user asked about a particular date or time.
+// Returns when the run starts and a description of the forecast time along with the conditions.
+fn get_conditions(
+    req: &Request,
+    provider: &dyn WeatherProvider,
+    loc: &Location,
+) -> Result<(Conditions, DateTime<Utc>, Option<String>), OutfitHandlerError> {
+    let (date, time) = when::parse(get_slot_value(req, "date"), get_slot_value(req, "time"));
+    if date.is_none() && time.is_none() {
+        return Ok((provider.current(loc)?, Utc::now(), None));
+    }
+    let forecast = provider.forecast(loc)?;
+    let now = forecast.now_local();
+    let run_time = weather::next_occurrence(now, date, time)?;
+    info!("Using forecast for {}", run_time);
+    let start = forecast.to_utc(run_time);
+    let conditions = forecast.at(start)?;
+    Ok((
+        conditions,
+        start,
+        Some(when::describe(run_time, now.date())),
+    ))
+}
+
+// Describe how the weather changes over a run of the given duration, if it changes enough to
+// matter
+fn get_trend_note(
+    provider: &dyn WeatherProvider,
+    loc: &Location,
+    params: &RunParameters,
+    start: DateTime<Utc>,
+    duration: RunDuration,
+) -> Result<Option<String>, OutfitHandlerError> {
+    let forecast = provider.forecast(loc)?;
+    let trend = Trend::new(params, &forecast, &GearCatalog::default(), start, duration)?;
+    Ok(trend.note)
+}
 
+// Respond with the speech generated for the user's location and preferences, handling errors
+// common to all intents
+fn respond_at<F>(req: &Request, title: &str, to_speech: F) -> anyhow::Result<Response>
+where
+    F: FnOnce(
+        &dyn WeatherProvider,
+        &Location,
+        &UserPreferences,
+    ) -> Result<String, OutfitHandlerError>,
+{
+    let provider = get_provider()?;
+
+    let preferences = get_preferences(req);
     info!("{:?}", preferences);
 
-    let speech: Result<String, OutfitHandlerError> = location::get(req)
-        .and_then(|loc| weather::get_current(&owm_api_key, &loc).map_err(|e| e.into()))
-        .and_then(|conditions| {
-            Outfit::new(&RunParameters::new(&conditions, &preferences)).map_err(|e| e.into())
-        })
-        .and_then(|outfit| outfit_to_speech(&outfit));
+    let speech: Result<String, OutfitHandlerError> =
+        location::get(req).and_then(|loc| to_speech(provider.as_ref(), &loc, &preferences));
 
     match speech {
         Ok(speech) => {
-            info!("Recommending outfit: {}", speech);
-            Ok(Response::simple("Outfit", &speech))
+            info!("Responding with: {}", speech);
+            Ok(Response::simple(title, &speech))
         }
         Err(OutfitHandlerError::NoLocationPermissions) => Ok(Response::end()
             .speech(Speech::plain(INSUFFICIENT_LOCATION_PERMISSION_TEXT))
@@ -126,13 +337,24 @@ pub fn handler(req: &Request) -> anyhow::Result<Response> {
                 "read::alexa:device:all:address:country_and_postal_code".to_string(),
                 "alexa::devices:all:geolocation:read".to_string(),
             ]))),
-        Err(OutfitHandlerError::OutfitPickerError(picker::Error::FetchWeather(e))) => {
+        Err(OutfitHandlerError::OutfitPickerError(e @ picker::Error::FetchWeather { .. })) => {
             error!("{}", e);
             Ok(Response::simple(
                 FETCH_WEATHER_ERROR_TITLE,
                 FETCH_WEATHER_ERROR_TEXT,
             ))
         }
+        Err(OutfitHandlerError::OutfitPickerError(e @ picker::Error::StaleWeather { .. })) => {
+            warn!("{}", e);
+            Ok(Response::simple(
+                STALE_WEATHER_TITLE,
+                &format!("{}. Please try again later.", e),
+            ))
+        }
+        // e.g. asking about a time beyond the end of the forecast
+        Err(OutfitHandlerError::OutfitPickerError(picker::Error::InvalidInput(message))) => {
+            Ok(Response::simple(NO_FORECAST_TITLE, &message))
+        }
         Err(e) => {
             error!("{}", e);
             Err(e.into())
@@ -140,6 +362,152 @@ pub fn handler(req: &Request) -> anyhow::Result<Response> {
     }
 }
 
+// Fetch the weather for the user's location and respond with the speech generated from the
+// resulting run parameters
+fn respond<F>(req: &Request, title: &str, to_speech: F) -> anyhow::Result<Response>
+where
+    F: FnOnce(&RunParameters) -> Result<String, OutfitHandlerError>,
+{
+    respond_at(req, title, |provider, loc, preferences| {
+        let (conditions, start, run_time) = get_conditions(req, provider, loc)?;
+        let age = check_freshness(conditions.observed_at)?;
+        // Air quality is only available for right now, and isn't worth failing over
+        let air_quality = match run_time {
+            Some(_) => None,
+            None => provider.air_quality(loc).unwrap_or_else(|e| {
+                warn!("Couldn't get the air quality: {}", e);
+                None
+            }),
+        };
+        let params = RunParameters::new(&conditions, preferences).with_air_quality(air_quality);
+        // Dangers come before anything about what to wear
+        let mut speech = safety::assess(&params)
+            .iter()
+            .map(|warning| format!("{} ", warning.message))
+            .collect::<String>();
+        speech.push_str(&to_speech(&params)?);
+        // Following the weather over the run is a bonus, so don't fail over it
+        if let Some(duration) = preferences.duration {
+            match get_trend_note(provider, loc, &params, start, duration) {
+                Ok(Some(note)) => write!(&mut speech, " {}", note)?,
+                Ok(None) => {}
+                Err(e) => warn!("Couldn't follow the forecast over the run: {}", e),
+            }
+        }
+        if let (Some(comfort), Some(dew_point)) =
+            (conditions.comfort(), conditions.estimated_dew_point())
+        {
+            if comfort >= Comfort::Humid {
+                write!(
+                    &mut speech,
+                    " It's {} out, with a dew point of {} degrees.",
+                    comfort,
+                    dew_point.round(preferences.units)
+                )?;
+            }
+        }
+        let uv_category = conditions.uv_category();
+        if uv_category >= UvCategory::High {
+            write!(
+                &mut speech,
+                " The UV index is {:.0}, which is {}.",
+                conditions.estimated_uv_index(),
+                uv_category
+            )?;
+        }
+        if let Some(age) = age.filter(|age| age.num_minutes() >= MENTION_AGE_MINUTES) {
+            write!(
+                &mut speech,
+                " That's based on weather from {}.",
+                weather::describe_age(age)
+            )?;
+        }
+        Ok(match run_time {
+            Some(run_time) => format!("For {}: {}", run_time, speech),
+            None => speech,
+        })
+    })
+}
+
+pub fn handler(req: &Request) -> anyhow::Result<Response> {
+    respond(req, "Outfit", |params| {
+        let outfit = match Outfit::new(params, &GearCatalog::default()) {
+            Ok(outfit) => outfit,
+            Err(picker::Error::InvalidOutfit { explanation, .. }) => {
+                return Ok(incomplete_outfit_to_speech(
+                    &explanation,
+                    params.preferences.units,
+                ))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(format!(
+            "{} {}",
+            temperature_to_speech(
+                params.effective_temperature_breakdown(),
+                params.preferences.units
+            ),
+            outfit_to_speech(&outfit)?
+        ))
+    })
+}
+
+// Explain why gear was or wasn't picked, e.g. "why no gloves?"
+pub fn why_handler(req: &Request) -> anyhow::Result<Response> {
+    respond(req, "Why", |params| {
+        let query = get_slot_value(req, "gear");
+        let explanation = match Outfit::explain(params, &GearCatalog::default()) {
+            Ok((_, explanation)) => explanation,
+            Err(picker::Error::InvalidOutfit { explanation, .. }) if query.is_none() => {
+                return Ok(incomplete_outfit_to_speech(
+                    &explanation,
+                    params.preferences.units,
+                ))
+            }
+            Err(picker::Error::InvalidOutfit { explanation, .. }) => *explanation,
+            Err(e) => return Err(e.into()),
+        };
+        Ok(explanation_to_speech(
+            &explanation,
+            query,
+            params.preferences.units,
+        ))
+    })
+}
+
+// Find the best time to run in the next day, or on the day the user asked about
+pub fn best_time_handler(req: &Request) -> anyhow::Result<Response> {
+    respond_at(req, "Best time to run", |provider, loc, preferences| {
+        let forecast = provider.forecast(loc)?;
+        check_freshness(forecast.issued_at)?;
+        let now = forecast.now_local();
+        let (date, _) = when::parse(get_slot_value(req, "date"), None);
+        let start = match date {
+            Some(date) if date > now.date() => forecast.to_utc(date.and_hms_opt(0, 0, 0).unwrap()),
+            _ => Utc::now(),
+        };
+        let plan = Plan::new(
+            &forecast,
+            preferences,
+            &GearCatalog::default(),
+            start,
+            start + Duration::hours(24),
+        )?;
+        let best = &plan.best;
+        let mut speech = format!(
+            "The best time to run is {}, when it'll be {} degrees and {}. {}",
+            when::describe(best.start.naive_local(), now.date()),
+            best.conditions.temperature.round(preferences.units),
+            best.conditions.weather,
+            outfit_to_speech(&plan.outfit)?
+        );
+        if let Some(note) = plan.coarse_step_note() {
+            write!(&mut speech, " {}", note)?;
+        }
+        Ok(speech)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,36 @@
+use crate::device_api;
+use crate::error::OutfitHandlerError;
+use alexa_sdk::Request;
+use log::{info, warn};
+use picker::units::Units;
+
+type Result<T> = std::result::Result<T, OutfitHandlerError>;
+
+fn fetch_temperature_unit(req: &Request) -> Result<String> {
+    let response = device_api::get(
+        req,
+        "/v2/devices/{device_id}/settings/System.temperatureUnit",
+    )?;
+    if response.status() != 200 {
+        return Err(OutfitHandlerError::InvalidAlexaRequest(Box::new(
+            req.clone(),
+        )));
+    }
+    Ok(serde_json::from_str::<String>(&response.text()?)?)
+}
+
+// Get the user's preferred units from their device settings, falling back to imperial units
+pub fn get_units(req: &Request) -> Units {
+    match fetch_temperature_unit(req).as_deref() {
+        Ok("CELSIUS") => Units::Metric,
+        Ok("FAHRENHEIT") => Units::Imperial,
+        Ok(unit) => {
+            warn!("Unknown temperature unit: {}", unit);
+            Units::default()
+        }
+        Err(e) => {
+            info!("Couldn't get temperature unit setting: {}", e);
+            Units::default()
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use log::warn;
+
+// Parse an AMAZON.DATE slot value. Only specific dates and weekends are supported; weekends are
+// assumed to mean Saturday.
+// See https://developer.amazon.com/en-US/docs/alexa/custom-skills/slot-type-reference.html#date
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(date);
+    }
+    if let Some(week) = value.strip_suffix("-WE") {
+        let mut parts = week.splitn(2, "-W");
+        let year = parts.next()?.parse().ok()?;
+        let week = parts.next()?.parse().ok()?;
+        return NaiveDate::from_isoywd_opt(year, week, Weekday::Sat);
+    }
+    warn!("Unsupported date: {}", value);
+    None
+}
+
+// Parse an AMAZON.TIME slot value, which is either a time like "06:00" or a part of the day
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    let (hour, minute) = match value {
+        "MO" => (7, 0),
+        "AF" => (14, 0),
+        "EV" => (18, 0),
+        "NI" => (21, 0),
+        _ => match NaiveTime::parse_from_str(value, "%H:%M") {
+            Ok(time) => return Some(time),
+            Err(_) => {
+                warn!("Unsupported time: {}", value);
+                return None;
+            }
+        },
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// The date and time of day that the user asked about, if any
+pub fn parse(date: Option<&str>, time: Option<&str>) -> (Option<NaiveDate>, Option<NaiveTime>) {
+    (date.and_then(parse_date), time.and_then(parse_time))
+}
+
+/// Describe a run time relative to `today`, e.g. "tomorrow at 6:00 AM"
+pub fn describe(time: NaiveDateTime, today: NaiveDate) -> String {
+    let day = if time.date() == today {
+        "today".to_string()
+    } else if time.date() == today + Duration::days(1) {
+        "tomorrow".to_string()
+    } else {
+        time.format("%A").to_string()
+    };
+    format!("{} at {}", day, time.format("%-I:%M %p"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let (date, time) = parse(Some("2020-10-19"), Some("MO"));
+        assert_eq!(NaiveDate::from_ymd_opt(2020, 10, 19), date);
+        assert_eq!(NaiveTime::from_hms_opt(7, 0, 0), time);
+
+        let (date, time) = parse(Some("2020-W43-WE"), Some("05:45"));
+        assert_eq!(NaiveDate::from_ymd_opt(2020, 10, 24), date);
+        assert_eq!(NaiveTime::from_hms_opt(5, 45, 0), time);
+
+        assert_eq!((None, None), parse(Some("2020-10"), None));
+    }
+}